@@ -3,7 +3,16 @@ use sha2::{Digest, Sha256};
 use hex;
 use serde::{Serialize, Deserialize};
 use crate::wallet::transaction::Transaction;
-use serde_json;
+use crate::blockchain::merkle::merkle_root;
+use crate::blockchain::pow::{hash_meets_target, target_from_bits, U256};
+use crate::encoding::{Decoder, Encoder, ENCODING_VERSION};
+use crate::errors::{BlockError, EncodingError, TransactionError};
+use std::collections::HashSet;
+
+/// Soma máxima do tamanho canônico das transações de um bloco, em bytes.
+pub const MAX_BLOCK_SIZE: usize = 100_000;
+/// Máximo de transações por bloco, contando a coinbase.
+pub const MAX_BLOCK_TRANSACTIONS: usize = 500;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Block {
@@ -11,81 +20,282 @@ pub struct Block {
     pub timestamp: i64,
     pub transactions: Vec<Transaction>,
     pub previous_hash: String,
+    // raiz de Merkle dos tx_hash, é o que o header assina das transações
+    pub merkle_root: String,
+    pub hash: String,
+    // target compacto contra o qual o bloco foi minerado
+    pub bits: u32,
+    pub nonce: u64,
+}
+
+/// Só os campos do header: é o que um cliente leve precisa para conferir o PoW.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub timestamp: i64,
+    pub previous_hash: String,
+    pub merkle_root: String,
     pub hash: String,
+    pub bits: u32,
     pub nonce: u64,
 }
 
+impl BlockHeader {
+    //confere o hash e o PoW sem precisar das transações
+    pub fn is_valid(&self) -> bool {
+        let recalculated = calculate_hash(
+            self.index,
+            self.timestamp,
+            &self.merkle_root,
+            &self.previous_hash,
+            self.bits,
+            self.nonce
+        );
+        recalculated == self.hash && hash_meets_target(&self.hash, self.bits)
+    }
+}
+
 impl Block {
     //create a new block
-    pub fn new(index: u64, transactions: Vec<Transaction>, previous_hash: String) -> Block {
+    pub fn new(index: u64, transactions: Vec<Transaction>, previous_hash: String, bits: u32) -> Block {
         let time_created = Utc::now().timestamp();
         let nonce: u64 = 0;
 
-        let hash = calculate_hash(index, time_created, &transactions, &previous_hash, nonce);
+        let merkle_root = compute_merkle_root(&transactions);
+        let hash = calculate_hash(index, time_created, &merkle_root, &previous_hash, bits, nonce);
 
         Block {
             index,
             timestamp: time_created,
             transactions,
             previous_hash,
+            merkle_root,
             hash,
+            bits,
             nonce,
         }
     }
 
-    //use that to add_block to the blockchain
-    pub fn mine_block(&mut self, difficulty: u32) {
-        let target_prefix = "0".repeat(difficulty as usize);
+    pub fn target(&self) -> Option<U256> {
+        target_from_bits(self.bits)
+    }
+
+    //confere se merkle_root bate com as transações do corpo
+    pub fn has_valid_merkle_root(&self) -> bool {
+        self.merkle_root == compute_merkle_root(&self.transactions)
+    }
+
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            index: self.index,
+            timestamp: self.timestamp,
+            previous_hash: self.previous_hash.clone(),
+            merkle_root: self.merkle_root.clone(),
+            hash: self.hash.clone(),
+            bits: self.bits,
+            nonce: self.nonce,
+        }
+    }
+
+    //hash só do header, não precisa das transações
+    pub fn header_hash(&self) -> String {
+        calculate_hash(
+            self.index,
+            self.timestamp,
+            &self.merkle_root,
+            &self.previous_hash,
+            self.bits,
+            self.nonce
+        )
+    }
+
+    /// Codificação binária canônica: header seguido das transações (cada uma com
+    /// prefixo de tamanho). O `hash` não vai junto, ele é recalculado do header.
+    #[allow(dead_code)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.bytes(&encode_header(
+            self.index,
+            self.timestamp,
+            &self.merkle_root,
+            &self.previous_hash,
+            self.bits,
+            self.nonce,
+        ));
+        encoder.u32(self.transactions.len() as u32);
+        for tx in &self.transactions {
+            encoder.bytes(&tx.to_bytes());
+        }
+        encoder.finish()
+    }
 
+    #[allow(dead_code)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Block, EncodingError> {
+        let mut decoder = Decoder::new(bytes);
+
+        let mut header = Decoder::new(decoder.bytes()?);
+        header.version()?;
+        let index = header.u64()?;
+        let timestamp = header.i64()?;
+        let merkle_root = header.string()?;
+        let previous_hash = header.string()?;
+        let bits = header.u32()?;
+        let nonce = header.u64()?;
+        header.finish()?;
+
+        let count = decoder.u32()?;
+        let mut transactions = Vec::new();
+        for _ in 0..count {
+            transactions.push(Transaction::from_bytes(decoder.bytes()?)?);
+        }
+        decoder.finish()?;
+
+        let hash = calculate_hash(index, timestamp, &merkle_root, &previous_hash, bits, nonce);
+        Ok(Block {
+            index,
+            timestamp,
+            transactions,
+            previous_hash,
+            merkle_root,
+            hash,
+            bits,
+            nonce,
+        })
+    }
+
+    //use that to add_block to the blockchain
+    pub fn mine_block(&mut self) {
+        self.merkle_root = compute_merkle_root(&self.transactions);
         loop {
             self.hash = calculate_hash(
                 self.index,
                 self.timestamp,
-                &self.transactions,
+                &self.merkle_root,
                 &self.previous_hash,
+                self.bits,
                 self.nonce
             );
 
-            if self.hash.starts_with(&target_prefix) {
+            if hash_meets_target(&self.hash, self.bits) {
                 break;
             }
             self.nonce += 1;
         }
     }
 
-    //verifica o hash do bloco e se a dificuldade está de acordo
-    pub fn is_valid(&self, difficulty: u32) -> bool {
-        // 1) Recalcular o hash com base em index, timestamp, transactions, previous_hash, nonce
-        let recalculated = calculate_hash(
-            self.index,
-            self.timestamp,
-            &self.transactions,
-            &self.previous_hash,
-            self.nonce
-        );
-        if recalculated != self.hash {
+    //mesmas checagens de is_valid, mas dizendo o que falhou
+    pub fn check(&self) -> Result<(), BlockError> {
+        if self.header_hash() != self.hash {
+            return Err(BlockError::InvalidHash);
+        }
+        if !self.has_valid_merkle_root() {
+            return Err(BlockError::InvalidMerkleRoot);
+        }
+        // repetir a última transação de um nível ímpar não muda a raiz (CVE-2012-2459):
+        // sem isso um corpo adulterado passaria com o hash do bloco honesto
+        self.check_duplicates()?;
+        if !hash_meets_target(&self.hash, self.bits) {
+            return Err(BlockError::InsufficientProofOfWork);
+        }
+        self.check_limits()
+    }
+
+    /// Toda transação que não é coinbase precisa de assinatura válida feita pela
+    /// chave do próprio from_address, e nenhuma
+    /// pode aparecer duas vezes. Saldos, nonces e a coinbase ficam com o Ledger.
+    pub fn check_transactions(&self) -> Result<(), BlockError> {
+        for tx in &self.transactions {
+            if !tx.is_coinbase() && tx.public_key.is_some() {
+                tx.check_address_binding()?;
+            }
+            if !tx.is_coinbase() && !tx.is_valid() {
+                return Err(BlockError::Transaction(TransactionError::InvalidSignature(format!(
+                    "Transaction {} has an invalid signature",
+                    tx.tx_hash()
+                ))));
+            }
+        }
+        self.check_duplicates()
+    }
+
+    /// Nenhuma transação pode aparecer duas vezes no bloco.
+    pub fn check_duplicates(&self) -> Result<(), BlockError> {
+        let mut seen = HashSet::new();
+        for tx in &self.transactions {
+            let tx_hash = tx.tx_hash();
+            if !seen.insert(tx_hash.clone()) {
+                return Err(BlockError::DuplicateTransaction(tx_hash));
+            }
+        }
+        Ok(())
+    }
+
+    /// Soma do tamanho canônico das transações.
+    pub fn transactions_size(&self) -> usize {
+        self.transactions.iter().map(|tx| tx.size()).sum()
+    }
+
+    pub fn check_limits(&self) -> Result<(), BlockError> {
+        if self.transactions.len() > MAX_BLOCK_TRANSACTIONS {
+            return Err(BlockError::TooManyTransactions {
+                count: self.transactions.len(),
+                max: MAX_BLOCK_TRANSACTIONS,
+            });
+        }
+        let size = self.transactions_size();
+        if size > MAX_BLOCK_SIZE {
+            return Err(BlockError::TooLarge { size, max: MAX_BLOCK_SIZE });
+        }
+        Ok(())
+    }
+
+    //verifica o hash do bloco, a raiz de Merkle e se ele está abaixo do target gravado em `bits`
+    pub fn is_valid(&self) -> bool {
+        // 1) Recalcular o hash do header (index, timestamp, merkle_root, previous_hash, bits, nonce)
+        if self.header_hash() != self.hash {
             return false;
         }
 
-        // 2) Checar se o hash começa com zeros (PoW)
-        let target_prefix = "0".repeat(difficulty as usize);
-        self.hash.starts_with(&target_prefix)
+        // 2) A raiz do header tem que corresponder às transações, sem nenhuma repetida
+        if !self.has_valid_merkle_root() || self.check_duplicates().is_err() {
+            return false;
+        }
+
+        // 3) Comparar o hash numericamente com o target (PoW)
+        if !hash_meets_target(&self.hash, self.bits) {
+            return false;
+        }
+
+        // 4) Limites de tamanho e de quantidade de transações
+        self.check_limits().is_ok()
     }
 }
 
-//calculate hash, used in mine_block above
-pub fn calculate_hash(index: u64, timestamp: i64, transactions: &Vec<Transaction>, previous_hash: &str, nonce: u64) -> String {
-    let mut hasher = Sha256::new();
+pub fn compute_merkle_root(transactions: &[Transaction]) -> String {
+    let hashes: Vec<String> = transactions.iter().map(|tx| tx.tx_hash()).collect();
+    merkle_root(&hashes)
+}
 
-    hasher.update(index.to_string());
-    hasher.update(timestamp.to_string());
-    let tx_string = serde_json::to_string(&transactions).unwrap();
-    hasher.update(tx_string);
-    hasher.update(previous_hash);
-    hasher.update(nonce.to_string());
+/// Codificação canônica do header, é o que o hash do bloco assina.
+pub fn encode_header(index: u64, timestamp: i64, merkle_root: &str, previous_hash: &str, bits: u32, nonce: u64) -> Vec<u8> {
+    Encoder::new()
+        .u8(ENCODING_VERSION)
+        .u64(index)
+        .i64(timestamp)
+        .string(merkle_root)
+        .string(previous_hash)
+        .u32(bits)
+        .u64(nonce)
+        .finish()
+}
+
+//calculate hash, used in mine_block above. Só campos do header entram no hash
+pub fn calculate_hash(index: u64, timestamp: i64, merkle_root: &str, previous_hash: &str, bits: u32, nonce: u64) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(encode_header(index, timestamp, merkle_root, previous_hash, bits, nonce));
 
     let result = hasher.finalize();
-    return hex::encode(result)
+    hex::encode(result)
 
 }
 
@@ -93,13 +303,19 @@ pub fn calculate_hash(index: u64, timestamp: i64, transactions: &Vec<Transaction
 mod tests {
     use super::*;
     use crate::wallet::transaction::Transaction; // se precisar
+    use crate::blockchain::pow::INITIAL_BITS;
+
+    // hash <= 2^252 (um zero hex) e hash <= 2^248 (dois zeros hex), mineram rápido nos testes
+    const EASY_BITS: u32 = 0x2010_0000;
+    const TWO_ZEROS_BITS: u32 = 0x2001_0000;
     // Se precisar criar transações de teste, importe também a wallet ou algo do tipo
 
     #[test]
     fn test_block_creation() {
-        let block = Block::new(0, vec![], "0".to_string());
+        let block = Block::new(0, vec![], "0".to_string(), INITIAL_BITS);
 
         assert_eq!(block.index, 0, "Index inicial deve ser 0");
+        assert_eq!(block.bits, INITIAL_BITS, "bits deve ser gravado no header");
         assert_eq!(block.transactions.len(), 0, "Sem transações no construtor");
         assert_eq!(block.previous_hash, "0");
         assert_eq!(block.nonce, 0, "nonce deve ser 0 por default");
@@ -109,41 +325,38 @@ mod tests {
 
     #[test]
     fn test_mine_block_increases_nonce_and_validates() {
-        let mut block = Block::new(1, vec![], "hash-do-bloco-anterior".to_string());
-        let difficulty = 2;
+        let mut block = Block::new(1, vec![], "hash-do-bloco-anterior".to_string(), TWO_ZEROS_BITS);
 
         // O hash inicial não necessariamente começa com "00"
         assert!(!block.hash.starts_with("00"));
 
         // Realiza a mineração
-        block.mine_block(difficulty);
+        block.mine_block();
 
-        // Verifica se agora começa com "00"
-        let prefix = "0".repeat(difficulty as usize);
-        assert!(block.hash.starts_with(&prefix), 
-            "Hash deve começar com {} zeros após minerar", difficulty);
+        // Verifica se agora está abaixo do target (2^248 => começa com "00")
+        assert!(block.hash.starts_with("00"), "Hash deve começar com 00 após minerar");
+        assert!(U256::from_hex(&block.hash).unwrap() <= block.target().unwrap());
 
         // O nonce deve ter aumentado (não ser zero)
         assert!(block.nonce > 0, "nonce deve ser incrementado durante a mineração");
 
-        // is_valid(difficulty) deve retornar true
-        assert!(block.is_valid(difficulty), "Bloco deve ser válido após minerar com essa dificuldade");
+        // is_valid() deve retornar true
+        assert!(block.is_valid(), "Bloco deve ser válido após minerar com esse target");
     }
 
     #[test]
     fn test_is_valid_after_tampering_nonce() {
-        let mut block = Block::new(2, vec![], "prev-hash".to_string());
-        let difficulty = 1;
-        block.mine_block(difficulty);
+        let mut block = Block::new(2, vec![], "prev-hash".to_string(), EASY_BITS);
+        block.mine_block();
 
         // Confirma que bloco está válido
-        assert!(block.is_valid(difficulty));
+        assert!(block.is_valid());
 
         // Tenta adulterar o nonce
         block.nonce += 1;
 
         // Agora deve falhar na verificação
-        assert!(!block.is_valid(difficulty), 
+        assert!(!block.is_valid(), 
             "Ao modificar nonce, o bloco deve ficar inválido");
     }
 
@@ -154,6 +367,8 @@ mod tests {
             from_address: "Alice".into(),
             to_address: "Bob".into(),
             amount: 50,
+            fee: 0,
+            nonce: 0,
             public_key: None,
             signature: None,
         };
@@ -161,37 +376,105 @@ mod tests {
             from_address: "Carol".into(),
             to_address: "Dave".into(),
             amount: 100,
+            fee: 0,
+            nonce: 0,
             public_key: None,
             signature: None,
         };
-        let mut block = Block::new(3, vec![tx1.clone(), tx2.clone()], "prev-hash".to_string());
+        let mut block = Block::new(3, vec![tx1.clone(), tx2.clone()], "prev-hash".to_string(), EASY_BITS);
 
-        let difficulty = 1;
-        block.mine_block(difficulty);
+        block.mine_block();
 
         // Bloco deve ser válido
-        assert!(block.is_valid(difficulty));
+        assert!(block.is_valid());
 
         // Agora adulteramos a primeira transação (ex.: amount = 999)
         block.transactions[0].amount = 999;
 
-        // Com essa adulteração, o hash guardado em 'block.hash' não corresponde mais. 
+        // Com essa adulteração, a merkle_root guardada não corresponde mais. 
         // is_valid deve retornar falso
-        assert!(!block.is_valid(difficulty), 
+        assert!(!block.is_valid(), 
             "Modificar as transações deve invalidar o bloco");
     }
 
+    #[test]
+    fn test_header_hash_does_not_need_transactions() {
+        let tx = Transaction {
+            from_address: "Alice".into(),
+            to_address: "Bob".into(),
+            amount: 50,
+            fee: 0,
+            nonce: 0,
+            public_key: None,
+            signature: None,
+        };
+        let mut block = Block::new(3, vec![tx], "prev-hash".to_string(), EASY_BITS);
+        block.mine_block();
+        assert_eq!(block.merkle_root, compute_merkle_root(&block.transactions));
+
+        // sem o corpo, o header continua verificável
+        let mut header_only = block.clone();
+        header_only.transactions.clear();
+        assert_eq!(header_only.header_hash(), block.hash);
+        assert!(!header_only.has_valid_merkle_root());
+    }
+
+    #[test]
+    fn test_is_valid_after_tampering_merkle_root() {
+        let mut block = Block::new(3, vec![], "prev-hash".to_string(), EASY_BITS);
+        block.mine_block();
+        assert!(block.is_valid());
+
+        block.merkle_root = "ab".repeat(32);
+        assert!(!block.is_valid(), "Modificar a merkle_root deve invalidar o bloco");
+    }
+
     #[test]
     fn test_is_valid_after_tampering_previous_hash() {
-        let mut block = Block::new(4, vec![], "prev-hash-abc".to_string());
-        let difficulty = 1;
-        block.mine_block(difficulty);
+        let mut block = Block::new(4, vec![], "prev-hash-abc".to_string(), EASY_BITS);
+        block.mine_block();
 
-        assert!(block.is_valid(difficulty));
+        assert!(block.is_valid());
 
         // Altera o previous_hash
         block.previous_hash = "tampered-hash".to_string();
-        assert!(!block.is_valid(difficulty), 
+        assert!(!block.is_valid(), 
             "Modificar o previous_hash deve invalidar o bloco");
     }
+
+    #[test]
+    fn test_is_valid_after_tampering_bits() {
+        let mut block = Block::new(5, vec![], "prev-hash".to_string(), EASY_BITS);
+        block.mine_block();
+        assert!(block.is_valid());
+
+        // trocar o target invalida o hash, mesmo que o novo target seja mais fácil
+        block.bits = 0x207f_ffff;
+        assert!(!block.is_valid(), "Modificar bits deve invalidar o bloco");
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let wallet = crate::wallet::wallet::generate_wallet();
+        let tx = Transaction::new_signed(&wallet, "Bob".to_string(), 5, 0).unwrap();
+        let coinbase = Transaction::new_coinbase("Miner".to_string(), 50, 7);
+        let mut block = Block::new(7, vec![coinbase, tx], "prev-hash".to_string(), EASY_BITS);
+        block.mine_block();
+
+        let decoded = Block::from_bytes(&block.to_bytes()).expect("roundtrip");
+        assert_eq!(decoded, block);
+        assert!(decoded.is_valid());
+    }
+
+    #[test]
+    fn test_binary_rejects_trailing_and_truncated_input() {
+        let mut block = Block::new(7, vec![], "prev-hash".to_string(), EASY_BITS);
+        block.mine_block();
+        let bytes = block.to_bytes();
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(Block::from_bytes(&trailing), Err(EncodingError::TrailingBytes(1)));
+        assert_eq!(Block::from_bytes(&bytes[..bytes.len() - 1]), Err(EncodingError::UnexpectedEnd));
+    }
 }
@@ -0,0 +1,180 @@
+use crate::blockchain::block::Block;
+use crate::blockchain::pow::{work_from_bits, U256};
+use std::collections::{HashMap, HashSet};
+
+/// Um bloco conhecido, com a altura e o trabalho acumulado do gênese até ele.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockEntry {
+    pub block: Block,
+    pub height: u64,
+    pub chain_work: U256,
+}
+
+/// Ponta de um ramo: bloco conhecido que ainda não tem filhos.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tip {
+    pub hash: String,
+    pub height: u64,
+    pub chain_work: U256,
+}
+
+/// Todos os blocos conhecidos com pai conhecido, indexados por hash: a chain
+/// ativa e os ramos laterais que perderam (ou ainda podem ganhar) o fork.
+#[derive(Debug, Default, Clone)]
+pub struct BlockIndex {
+    entries: HashMap<String, BlockEntry>,
+    children: HashMap<String, Vec<String>>,
+    tips: HashSet<String>,
+}
+
+impl BlockIndex {
+    pub fn new() -> Self {
+        BlockIndex::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&BlockEntry> {
+        self.entries.get(hash)
+    }
+
+    /// Indexa `block`. O primeiro bloco vira a raiz (gênese); os seguintes
+    /// precisam do pai já indexado. Devolve false se não entrou.
+    pub fn insert(&mut self, block: Block) -> bool {
+        if self.contains(&block.hash) {
+            return false;
+        }
+        let (height, parent_work) = match self.entries.get(&block.previous_hash) {
+            Some(parent) => (parent.height + 1, parent.chain_work),
+            None if self.entries.is_empty() => (0, U256::ZERO),
+            None => return false,
+        };
+
+        let hash = block.hash.clone();
+        self.tips.remove(&block.previous_hash);
+        self.tips.insert(hash.clone());
+        self.children.entry(block.previous_hash.clone()).or_default().push(hash.clone());
+        let chain_work = parent_work.saturating_add(work_from_bits(block.bits));
+        self.entries.insert(hash, BlockEntry { block, height, chain_work });
+        true
+    }
+
+    /// Tira do índice uma ponta (bloco sem filhos); o pai volta a ser ponta
+    /// se não tiver outros filhos.
+    pub fn remove_tip(&mut self, hash: &str) -> Option<Block> {
+        if !self.tips.contains(hash) {
+            return None;
+        }
+        let entry = self.entries.remove(hash)?;
+        self.tips.remove(hash);
+
+        let parent_hash = &entry.block.previous_hash;
+        if let Some(siblings) = self.children.get_mut(parent_hash) {
+            siblings.retain(|h| h != hash);
+            if siblings.is_empty() {
+                self.children.remove(parent_hash);
+                if self.entries.contains_key(parent_hash) {
+                    self.tips.insert(parent_hash.clone());
+                }
+            }
+        }
+        Some(entry.block)
+    }
+
+    /// Todas as pontas, da com mais trabalho para a com menos; no empate, pelo
+    /// hash, para todo nó escolher a mesma.
+    pub fn tips(&self) -> Vec<Tip> {
+        let mut tips: Vec<Tip> = self
+            .tips
+            .iter()
+            .map(|hash| {
+                let entry = &self.entries[hash];
+                Tip { hash: hash.clone(), height: entry.height, chain_work: entry.chain_work }
+            })
+            .collect();
+        tips.sort_by(|a, b| b.chain_work.cmp(&a.chain_work).then_with(|| a.hash.cmp(&b.hash)));
+        tips
+    }
+
+    /// Ponta com mais trabalho acumulado.
+    pub fn best_tip(&self) -> Option<Tip> {
+        self.tips().into_iter().next()
+    }
+
+    /// Blocos do gênese até `hash`, inclusive.
+    pub fn branch_to(&self, hash: &str) -> Vec<Block> {
+        self.recent_blocks(hash, usize::MAX)
+    }
+
+    /// Os últimos `count` blocos do ramo que termina em `hash` (inclusive), em ordem.
+    pub fn recent_blocks(&self, hash: &str, count: usize) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut current = self.entries.get(hash);
+        while let Some(entry) = current.filter(|_| blocks.len() < count) {
+            blocks.push(entry.block.clone());
+            current = self.entries.get(&entry.block.previous_hash);
+        }
+        blocks.reverse();
+        blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::pow::INITIAL_BITS;
+
+    fn child(index: u64, parent: &Block, nonce: u64) -> Block {
+        let mut block = Block::new(index, vec![], parent.hash.clone(), INITIAL_BITS);
+        // só para ter hashes diferentes na mesma altura
+        block.nonce = nonce;
+        block.hash = block.header_hash();
+        block
+    }
+
+    #[test]
+    fn test_tracks_competing_tips() {
+        let genesis = Block::new(0, vec![], "0".to_string(), INITIAL_BITS);
+        let a1 = child(1, &genesis, 1);
+        let b1 = child(1, &genesis, 2);
+        let b2 = child(2, &b1, 3);
+
+        let mut index = BlockIndex::new();
+        assert!(index.insert(genesis.clone()));
+        assert!(index.insert(a1.clone()));
+        assert!(index.insert(b1.clone()));
+        assert!(!index.insert(b1.clone()));
+        assert!(!index.insert(child(5, &b2, 4)), "pai desconhecido não entra");
+        assert_eq!(index.tips().len(), 2);
+
+        assert!(index.insert(b2.clone()));
+        let best = index.best_tip().unwrap();
+        assert_eq!(best.hash, b2.hash);
+        assert_eq!(best.height, 2);
+        assert_eq!(index.tips()[1].hash, a1.hash);
+        assert_eq!(index.branch_to(&b2.hash), vec![genesis.clone(), b1.clone(), b2.clone()]);
+        assert_eq!(index.recent_blocks(&b2.hash, 2), vec![b1.clone(), b2.clone()]);
+
+        // sem b2, b1 volta a ser ponta
+        assert_eq!(index.remove_tip(&b2.hash), Some(b2));
+        assert!(index.remove_tip(&genesis.hash).is_none());
+        assert_eq!(index.tips().len(), 2);
+        assert!(index.tips().iter().any(|tip| tip.hash == b1.hash));
+
+        // mesmo trabalho: ganha o menor hash, não a ordem do HashSet
+        let (low, high) = if a1.hash < b1.hash { (&a1, &b1) } else { (&b1, &a1) };
+        let hashes: Vec<String> = index.tips().into_iter().map(|tip| tip.hash).collect();
+        assert_eq!(hashes, vec![low.hash.clone(), high.hash.clone()]);
+    }
+}
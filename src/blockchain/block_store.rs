@@ -0,0 +1,671 @@
+use crate::blockchain::block::Block;
+use crate::blockchain::blockchain::TxIndex;
+use crate::errors::StorageError;
+use crate::wallet::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Início de todo registro; ajuda a achar onde uma escrita parou no meio.
+pub const RECORD_MAGIC: [u8; 4] = *b"BPOW";
+/// magic (4) + tamanho do payload (u32 big-endian) + checksum (4)
+pub const RECORD_HEADER_LEN: usize = 12;
+/// Um segmento novo é aberto quando o atual passaria deste tamanho.
+pub const MAX_SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+
+const BLOCKS_DIR: &str = "blocks";
+const MEMPOOL_FILE: &str = "mempool.dat";
+const TX_INDEX_FILE: &str = "txindex.dat";
+
+// tipos de registro do journal do mempool
+const MEMPOOL_ADD: u8 = 0;
+const MEMPOOL_REMOVE: u8 = 1;
+/// Abaixo disso o journal do mempool nunca é compactado.
+pub const MEMPOOL_COMPACT_MIN_RECORDS: usize = 1_000;
+/// Com mais atualizações que isso o txindex.dat é regravado como um índice só.
+pub const TX_INDEX_COMPACT_RECORDS: usize = 1_000;
+
+/// Registro do txindex.dat: tira do índice as transações dos blocos `disconnected`,
+/// acrescenta `added`, e o resultado vale para a chain que termina em `tip`. Um
+/// índice inteiro é um registro sem `disconnected`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxIndexUpdate {
+    pub tip: String,
+    pub disconnected: Vec<String>,
+    pub added: TxIndex,
+}
+
+/// Onde um bloco está gravado.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordLocation {
+    pub segment: u32,
+    pub offset: u64,
+    pub len: u32,
+}
+
+/// Blocos gravados em sequência em arquivos de segmento (`blocks/blk00000.dat`, ...).
+/// Cada registro é magic + tamanho + checksum + `Block::to_bytes`, e cada append
+/// só volta depois do fsync. Na abertura os segmentos são lidos inteiros: o índice
+/// por hash e por altura é remontado e um registro incompleto no fim do último
+/// segmento (escrita interrompida) é cortado.
+///
+/// O mempool fica num journal à parte (`mempool.dat`): cada transação que entra ou
+/// sai vira um registro no fim do arquivo, e o arquivo só é regravado inteiro
+/// quando a maior parte dos registros já não vale mais. O índice de transações
+/// (`txindex.dat`) também só recebe registros no fim, um por bloco conectado ou reorg.
+#[derive(Debug)]
+pub struct BlockStore {
+    dir: PathBuf,
+    max_segment_size: u64,
+    file: File,
+    segment: u32,
+    segment_len: u64,
+    by_hash: HashMap<String, RecordLocation>,
+    by_height: HashMap<u64, Vec<String>>,
+    mempool_file: File,
+    // registros no journal do mempool, contando os que já foram desfeitos
+    mempool_records: usize,
+    tx_index_file: File,
+    // registros no txindex.dat; o primeiro normalmente é o índice inteiro
+    tx_index_records: usize,
+}
+
+impl BlockStore {
+    /// Abre (ou cria) o store em `dir` e devolve os blocos gravados, na ordem em que
+    /// foram gravados (pai sempre antes do filho).
+    pub fn open(dir: impl AsRef<Path>) -> Result<(BlockStore, Vec<Block>), StorageError> {
+        BlockStore::open_with_segment_size(dir, MAX_SEGMENT_SIZE)
+    }
+
+    pub fn open_with_segment_size(
+        dir: impl AsRef<Path>,
+        max_segment_size: u64,
+    ) -> Result<(BlockStore, Vec<Block>), StorageError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(dir.join(BLOCKS_DIR))?;
+
+        let mut segments = list_segments(&dir)?;
+        if segments.is_empty() {
+            segments.push(0);
+        }
+        let last = *segments.last().unwrap();
+
+        let mut by_hash = HashMap::new();
+        let mut by_height: HashMap<u64, Vec<String>> = HashMap::new();
+        let mut blocks = Vec::new();
+        let mut segment_len = 0;
+        for &segment in &segments {
+            let path = segment_path(&dir, segment);
+            let bytes = if path.exists() { fs::read(&path)? } else { Vec::new() };
+            let (records, valid_len) = scan_records(&bytes);
+
+            if valid_len < bytes.len() {
+                if segment != last {
+                    return Err(StorageError::Corrupt {
+                        segment,
+                        offset: valid_len as u64,
+                        reason: "invalid record in the middle of the store".to_string(),
+                    });
+                }
+                // escrita interrompida: o que vem depois do último registro íntegro é descartado
+                println!(
+                    "Segmento {}: cortando {} bytes de um registro incompleto no fim",
+                    segment,
+                    bytes.len() - valid_len
+                );
+                let file = OpenOptions::new().write(true).open(&path)?;
+                file.set_len(valid_len as u64)?;
+                file.sync_all()?;
+            }
+
+            for record in records {
+                let offset = (record.start - RECORD_HEADER_LEN) as u64;
+                // checksum bateu mas não decodifica: não é escrita interrompida, é outro formato
+                let block = Block::from_bytes(&bytes[record.clone()]).map_err(|e| StorageError::Corrupt {
+                    segment,
+                    offset,
+                    reason: e.to_string(),
+                })?;
+                let location = RecordLocation { segment, offset, len: record.len() as u32 };
+                by_height.entry(block.index).or_default().push(block.hash.clone());
+                by_hash.insert(block.hash.clone(), location);
+                blocks.push(block);
+            }
+            segment_len = valid_len as u64;
+        }
+
+        let file = open_segment(&dir, last)?;
+        let mempool_file = open_append(&dir.join(MEMPOOL_FILE))?;
+        let tx_index_file = open_append(&dir.join(TX_INDEX_FILE))?;
+        let store = BlockStore {
+            dir,
+            max_segment_size,
+            file,
+            segment: last,
+            segment_len,
+            by_hash,
+            by_height,
+            mempool_file,
+            mempool_records: 0,
+            tx_index_file,
+            tx_index_records: 0,
+        };
+        Ok((store, blocks))
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_hash.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.by_hash.is_empty()
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.by_hash.contains_key(hash)
+    }
+
+    #[allow(dead_code)]
+    pub fn location(&self, hash: &str) -> Option<RecordLocation> {
+        self.by_hash.get(hash).copied()
+    }
+
+    /// Hashes gravados nessa altura (mais de um quando houve fork).
+    pub fn hashes_at(&self, height: u64) -> &[String] {
+        self.by_height.get(&height).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Grava o bloco no fim do segmento atual e faz fsync. Bloco já gravado é ignorado.
+    pub fn append(&mut self, block: &Block) -> Result<RecordLocation, StorageError> {
+        if let Some(location) = self.by_hash.get(&block.hash) {
+            return Ok(*location);
+        }
+
+        let record = encode_record(&block.to_bytes());
+        if self.segment_len > 0 && self.segment_len + record.len() as u64 > self.max_segment_size {
+            self.segment += 1;
+            self.file = open_segment(&self.dir, self.segment)?;
+            self.segment_len = 0;
+            // o arquivo novo só existe de verdade depois do fsync do diretório
+            File::open(self.dir.join(BLOCKS_DIR))?.sync_all()?;
+        }
+
+        let written = self.file.write_all(&record).and_then(|()| self.file.sync_data());
+        if let Err(e) = written {
+            self.discard_partial_record();
+            return Err(e.into());
+        }
+
+        let location = RecordLocation {
+            segment: self.segment,
+            offset: self.segment_len,
+            len: (record.len() - RECORD_HEADER_LEN) as u32,
+        };
+        self.segment_len += record.len() as u64;
+        self.by_height.entry(block.index).or_default().push(block.hash.clone());
+        self.by_hash.insert(block.hash.clone(), location);
+        Ok(location)
+    }
+
+    // corta o que uma escrita que falhou deixou depois do último registro íntegro;
+    // sem isso o próximo append iria parar depois do lixo e a reabertura descartaria
+    // o bloco junto com ele
+    fn discard_partial_record(&self) {
+        if let Err(e) = self.file.set_len(self.segment_len).and_then(|()| self.file.sync_data()) {
+            eprintln!("Segmento {}: não foi possível cortar o registro incompleto: {}", self.segment, e);
+        }
+    }
+
+    /// Lê um bloco do disco pelo hash.
+    #[allow(dead_code)]
+    pub fn read(&self, hash: &str) -> Result<Option<Block>, StorageError> {
+        let Some(location) = self.location(hash) else {
+            return Ok(None);
+        };
+        let bytes = fs::read(segment_path(&self.dir, location.segment))?;
+        let start = location.offset as usize + RECORD_HEADER_LEN;
+        let payload = bytes
+            .get(start..start + location.len as usize)
+            .ok_or_else(|| StorageError::Corrupt {
+                segment: location.segment,
+                offset: location.offset,
+                reason: "record past the end of the segment".to_string(),
+            })?;
+        let block = Block::from_bytes(payload).map_err(|e| StorageError::Corrupt {
+            segment: location.segment,
+            offset: location.offset,
+            reason: e.to_string(),
+        })?;
+        Ok(Some(block))
+    }
+
+    /// Acrescenta uma atualização no fim do índice de transações. Sem fsync: se as
+    /// últimas se perderem o tip gravado fica para trás e o índice é refeito.
+    pub fn append_tx_index(&mut self, update: &TxIndexUpdate) -> Result<(), StorageError> {
+        let payload = serde_json::to_vec(update).map_err(|e| StorageError::Io(e.to_string()))?;
+        self.tx_index_file.write_all(&encode_record(&payload))?;
+        self.tx_index_records += 1;
+        Ok(())
+    }
+
+    /// Atualizações demais em cima do índice: hora de regravar com `save_tx_index`
+    /// para o replay na abertura não crescer com a idade do nó.
+    pub fn tx_index_is_stale(&self) -> bool {
+        self.tx_index_records > TX_INDEX_COMPACT_RECORDS
+    }
+
+    /// Troca o índice gravado pelo índice inteiro da chain que termina em `tip`,
+    /// num único registro escrito num temporário e renomeado.
+    pub fn save_tx_index(&mut self, tip: &str, transactions: &TxIndex) -> Result<(), StorageError> {
+        let path = self.dir.join(TX_INDEX_FILE);
+        let update = TxIndexUpdate { tip: tip.to_string(), disconnected: Vec::new(), added: transactions.clone() };
+        let payload = serde_json::to_vec(&update).map_err(|e| StorageError::Io(e.to_string()))?;
+        write_atomically(&path, &encode_record(&payload))?;
+        self.tx_index_file = open_append(&path)?;
+        self.tx_index_records = 1;
+        Ok(())
+    }
+
+    /// Índice remontado a partir das atualizações gravadas e o tip a que ele
+    /// corresponde; None se não houver nenhuma ou se algum registro estiver
+    /// cortado ou ilegível (aí ele é refeito a partir dos blocos).
+    pub fn load_tx_index(&mut self) -> Result<Option<(String, TxIndex)>, StorageError> {
+        let path = self.dir.join(TX_INDEX_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(path)?;
+        let (records, valid_len) = scan_records(&bytes);
+        self.tx_index_records = records.len();
+        if records.is_empty() || valid_len < bytes.len() {
+            return Ok(None);
+        }
+
+        let mut tip = String::new();
+        let mut transactions = TxIndex::new();
+        for record in records {
+            let Ok(update) = serde_json::from_slice::<TxIndexUpdate>(&bytes[record]) else {
+                return Ok(None);
+            };
+            if !update.disconnected.is_empty() {
+                let disconnected: HashSet<String> = update.disconnected.into_iter().collect();
+                transactions.retain(|_, location| !disconnected.contains(&location.block_hash));
+            }
+            transactions.extend(update.added);
+            tip = update.tip;
+        }
+        Ok(Some((tip, transactions)))
+    }
+
+    /// Acrescenta ao journal do mempool as transações que entraram e os hashes das
+    /// que saíram. Sem fsync: perder as últimas pendentes num crash é aceitável,
+    /// elas voltam pela rede.
+    pub fn append_mempool(&mut self, added: &[Transaction], removed: &[String]) -> Result<(), StorageError> {
+        if added.is_empty() && removed.is_empty() {
+            return Ok(());
+        }
+        let mut bytes = Vec::new();
+        for tx in added {
+            bytes.extend(encode_record(&[&[MEMPOOL_ADD][..], &tx.to_bytes()].concat()));
+        }
+        for tx_hash in removed {
+            bytes.extend(encode_record(&[&[MEMPOOL_REMOVE][..], tx_hash.as_bytes()].concat()));
+        }
+        self.mempool_file.write_all(&bytes)?;
+        self.mempool_records += added.len() + removed.len();
+        Ok(())
+    }
+
+    /// Compactação valendo a pena: o journal passou do mínimo e tem mais que o
+    /// dobro de registros das `live` transações que ainda estão no mempool.
+    pub fn mempool_journal_is_stale(&self, live: usize) -> bool {
+        self.mempool_records > MEMPOOL_COMPACT_MIN_RECORDS.max(2 * live)
+    }
+
+    /// Troca o journal por um só com `txs` (arquivo temporário + rename).
+    pub fn save_mempool(&mut self, txs: &[Transaction]) -> Result<(), StorageError> {
+        let path = self.dir.join(MEMPOOL_FILE);
+        let mut bytes = Vec::new();
+        for tx in txs {
+            bytes.extend(encode_record(&[&[MEMPOOL_ADD][..], &tx.to_bytes()].concat()));
+        }
+        write_atomically(&path, &bytes)?;
+        // o handle antigo aponta para o arquivo que o rename substituiu
+        self.mempool_file = open_append(&path)?;
+        self.mempool_records = txs.len();
+        Ok(())
+    }
+
+    /// Pendentes que o journal deixou no mempool, na ordem em que entraram.
+    /// Registros ilegíveis são ignorados e a leitura para num registro cortado.
+    pub fn load_mempool(&mut self) -> Result<Vec<Transaction>, StorageError> {
+        let path = self.dir.join(MEMPOOL_FILE);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let bytes = fs::read(path)?;
+        let (records, _) = scan_records(&bytes);
+        self.mempool_records = records.len();
+
+        let mut txs: Vec<Option<Transaction>> = Vec::new();
+        let mut positions = HashMap::new();
+        for record in records {
+            let Some((&kind, payload)) = bytes[record].split_first() else {
+                continue;
+            };
+            match kind {
+                MEMPOOL_ADD => {
+                    if let Ok(tx) = Transaction::from_bytes(payload) {
+                        positions.insert(tx.tx_hash(), txs.len());
+                        txs.push(Some(tx));
+                    }
+                }
+                MEMPOOL_REMOVE => {
+                    let position = std::str::from_utf8(payload).ok().and_then(|hash| positions.remove(hash));
+                    if let Some(position) = position {
+                        txs[position] = None;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(txs.into_iter().flatten().collect())
+    }
+}
+
+// quem lê vê o arquivo antigo ou o novo inteiro, nunca metade, mesmo depois de
+// uma queda de energia: o conteúdo vai para o disco antes do rename, e o rename
+// só vale depois do fsync do diretório
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), StorageError> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn segment_path(dir: &Path, segment: u32) -> PathBuf {
+    dir.join(BLOCKS_DIR).join(format!("blk{:05}.dat", segment))
+}
+
+fn open_segment(dir: &Path, segment: u32) -> Result<File, StorageError> {
+    open_append(&segment_path(dir, segment))
+}
+
+fn open_append(path: &Path) -> Result<File, StorageError> {
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+// números dos segmentos existentes, em ordem
+fn list_segments(dir: &Path) -> Result<Vec<u32>, StorageError> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir.join(BLOCKS_DIR))? {
+        let name = entry?.file_name();
+        let number = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("blk"))
+            .and_then(|name| name.strip_suffix(".dat"))
+            .and_then(|number| number.parse().ok());
+        if let Some(number) = number {
+            segments.push(number);
+        }
+    }
+    segments.sort_unstable();
+    Ok(segments)
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let digest = Sha256::digest(payload);
+    [digest[0], digest[1], digest[2], digest[3]]
+}
+
+pub fn encode_record(payload: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+    record.extend_from_slice(&RECORD_MAGIC);
+    record.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    record.extend_from_slice(&checksum(payload));
+    record.extend_from_slice(payload);
+    record
+}
+
+/// Percorre os registros de `bytes` e devolve a posição do payload de cada um,
+/// mais até onde os registros estão íntegros. Para no primeiro magic errado,
+/// registro cortado ou checksum que não bate.
+pub fn scan_records(bytes: &[u8]) -> (Vec<Range<usize>>, usize) {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = bytes.get(offset..offset + RECORD_HEADER_LEN) {
+        if header[..4] != RECORD_MAGIC {
+            break;
+        }
+        let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let start = offset + RECORD_HEADER_LEN;
+        let Some(payload) = bytes.get(start..start + len) else {
+            break;
+        };
+        if checksum(payload) != header[8..12] {
+            break;
+        }
+        records.push(start..start + len);
+        offset = start + len;
+    }
+    (records, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::blockchain::{Blockchain, TxLocation};
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("blockchainpow-store-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn mined_blocks(count: usize) -> Vec<Block> {
+        let mut blockchain = Blockchain::new();
+        for _ in 0..count {
+            blockchain.add_block("miner");
+        }
+        blockchain.blocks[1..].to_vec()
+    }
+
+    #[test]
+    fn test_append_and_reopen() {
+        let dir = temp_dir();
+        let blocks = mined_blocks(3);
+        {
+            let (mut store, loaded) = BlockStore::open(&dir).unwrap();
+            assert!(loaded.is_empty());
+            for block in &blocks {
+                store.append(block).unwrap();
+            }
+            store.append(&blocks[0]).unwrap();
+            assert_eq!(store.len(), 3);
+        }
+
+        let (store, loaded) = BlockStore::open(&dir).unwrap();
+        assert_eq!(loaded, blocks);
+        assert_eq!(store.hashes_at(2), &[blocks[1].hash.clone()]);
+        assert_eq!(store.read(&blocks[2].hash).unwrap(), Some(blocks[2].clone()));
+        assert_eq!(store.read("unknown").unwrap(), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_torn_tail_is_truncated() {
+        let dir = temp_dir();
+        let blocks = mined_blocks(2);
+        let valid_len = {
+            let (mut store, _) = BlockStore::open(&dir).unwrap();
+            store.append(&blocks[0]).unwrap();
+            store.segment_len
+        };
+
+        // metade de um registro, como se o processo tivesse caído no meio da escrita
+        let record = encode_record(&blocks[1].to_bytes());
+        let path = segment_path(&dir, 0);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&record[..record.len() / 2]).unwrap();
+        drop(file);
+
+        let (mut store, loaded) = BlockStore::open(&dir).unwrap();
+        assert_eq!(loaded, vec![blocks[0].clone()]);
+        assert_eq!(fs::metadata(&path).unwrap().len(), valid_len);
+
+        store.append(&blocks[1]).unwrap();
+        let (_, loaded) = BlockStore::open(&dir).unwrap();
+        assert_eq!(loaded, blocks);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_write_is_rolled_back() {
+        let dir = temp_dir();
+        let blocks = mined_blocks(2);
+        let (mut store, _) = BlockStore::open(&dir).unwrap();
+        store.append(&blocks[0]).unwrap();
+
+        // o que sobraria de um write_all que falhou no meio
+        let record = encode_record(&blocks[1].to_bytes());
+        store.file.write_all(&record[..record.len() / 2]).unwrap();
+        store.discard_partial_record();
+        assert_eq!(fs::metadata(segment_path(&dir, 0)).unwrap().len(), store.segment_len);
+
+        store.append(&blocks[1]).unwrap();
+        drop(store);
+        let (_, loaded) = BlockStore::open(&dir).unwrap();
+        assert_eq!(loaded, blocks);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_segments_roll_over() {
+        let dir = temp_dir();
+        let blocks = mined_blocks(3);
+        {
+            // cabe um registro por segmento
+            let (mut store, _) = BlockStore::open_with_segment_size(&dir, 1).unwrap();
+            for block in &blocks {
+                store.append(block).unwrap();
+            }
+            assert_eq!(store.location(&blocks[2].hash).unwrap().segment, 2);
+        }
+        assert_eq!(list_segments(&dir).unwrap(), vec![0, 1, 2]);
+
+        // corrupção fora do último segmento não é escrita interrompida
+        let path = segment_path(&dir, 0);
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&path, bytes).unwrap();
+        assert!(matches!(
+            BlockStore::open_with_segment_size(&dir, 1),
+            Err(StorageError::Corrupt { segment: 0, offset: 0, .. })
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_tx_index_roundtrip() {
+        let dir = temp_dir();
+        let (mut store, _) = BlockStore::open(&dir).unwrap();
+        assert_eq!(store.load_tx_index().unwrap(), None);
+
+        let at = |block_hash: &str, height| TxLocation { block_hash: block_hash.to_string(), height, position: 0 };
+        let index = HashMap::from([("tx".to_string(), at("abc", 3))]);
+        store.save_tx_index("abc", &index).unwrap();
+        assert_eq!(store.load_tx_index().unwrap(), Some(("abc".to_string(), index)));
+
+        // um bloco conectado, depois um reorg que troca ele por outro
+        let connect = TxIndexUpdate {
+            tip: "def".to_string(),
+            disconnected: vec![],
+            added: HashMap::from([("tx2".to_string(), at("def", 4))]),
+        };
+        let reorg = TxIndexUpdate {
+            tip: "xyz".to_string(),
+            disconnected: vec!["def".to_string()],
+            added: HashMap::from([("tx3".to_string(), at("xyz", 4))]),
+        };
+        store.append_tx_index(&connect).unwrap();
+        store.append_tx_index(&reorg).unwrap();
+        let expected = HashMap::from([("tx".to_string(), at("abc", 3)), ("tx3".to_string(), at("xyz", 4))]);
+        assert_eq!(store.load_tx_index().unwrap(), Some(("xyz".to_string(), expected.clone())));
+        assert_eq!(store.tx_index_records, 3);
+
+        // passou do limite: regravado como um registro só, com o mesmo conteúdo
+        store.tx_index_records = TX_INDEX_COMPACT_RECORDS + 1;
+        assert!(store.tx_index_is_stale());
+        store.save_tx_index("xyz", &expected).unwrap();
+        assert!(!store.tx_index_is_stale());
+        assert_eq!(store.load_tx_index().unwrap(), Some(("xyz".to_string(), expected)));
+        assert_eq!(store.tx_index_records, 1);
+
+        // atualização cortada no meio: o tip gravado não é confiável
+        let path = dir.join(TX_INDEX_FILE);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&encode_record(b"{}")[..6]).unwrap();
+        assert_eq!(store.load_tx_index().unwrap(), None);
+
+        // arquivo corrompido: o índice é refeito, não é erro
+        fs::write(&path, b"lixo").unwrap();
+        assert_eq!(store.load_tx_index().unwrap(), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_mempool_roundtrip() {
+        let dir = temp_dir();
+        let alice = crate::wallet::wallet::generate_wallet();
+        let txs = vec![
+            Transaction::new_signed(&alice, "Bob".to_string(), 1, 0).unwrap(),
+            Transaction::new_signed(&alice, "Bob".to_string(), 2, 1).unwrap(),
+        ];
+        let (mut store, _) = BlockStore::open(&dir).unwrap();
+        assert!(store.load_mempool().unwrap().is_empty());
+        store.save_mempool(&txs).unwrap();
+        assert_eq!(store.load_mempool().unwrap(), txs);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_mempool_journal_replays_and_compacts() {
+        let dir = temp_dir();
+        let alice = crate::wallet::wallet::generate_wallet();
+        let txs: Vec<Transaction> = (0..3)
+            .map(|nonce| Transaction::new_signed(&alice, "Bob".to_string(), 1, nonce).unwrap())
+            .collect();
+        let (mut store, _) = BlockStore::open(&dir).unwrap();
+        store.append_mempool(&txs, &[]).unwrap();
+        store.append_mempool(&[], &[txs[1].tx_hash()]).unwrap();
+        let size = fs::metadata(dir.join(MEMPOOL_FILE)).unwrap().len();
+
+        // saiu e não volta; a ordem das que ficaram é a de entrada
+        let (mut reopened, _) = BlockStore::open(&dir).unwrap();
+        let live = reopened.load_mempool().unwrap();
+        assert_eq!(live, vec![txs[0].clone(), txs[2].clone()]);
+        assert_eq!(reopened.mempool_records, 4);
+        assert!(!reopened.mempool_journal_is_stale(live.len()));
+
+        reopened.save_mempool(&live).unwrap();
+        assert!(fs::metadata(dir.join(MEMPOOL_FILE)).unwrap().len() < size);
+        // continua acrescentando no arquivo novo
+        reopened.append_mempool(&[], &[txs[0].tx_hash()]).unwrap();
+        assert_eq!(reopened.load_mempool().unwrap(), vec![txs[2].clone()]);
+
+        reopened.mempool_records = MEMPOOL_COMPACT_MIN_RECORDS + 1;
+        assert!(reopened.mempool_journal_is_stale(1));
+        assert!(!reopened.mempool_journal_is_stale(MEMPOOL_COMPACT_MIN_RECORDS));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,117 +1,691 @@
-use crate::blockchain::block::Block;
+use crate::blockchain::block::{Block, MAX_BLOCK_SIZE, MAX_BLOCK_TRANSACTIONS};
+use crate::blockchain::block_index::{BlockIndex, Tip};
+use crate::blockchain::block_store::{BlockStore, TxIndexUpdate};
+use chrono::Utc;
 use serde::{Serialize, Deserialize};
-use crate::wallet::transaction::Transaction;
-use crate::blockchain::block::calculate_hash;
+use crate::wallet::transaction::{cmp_fee_rates, Transaction};
+use crate::blockchain::ledger::Ledger;
+use crate::blockchain::mempool::Mempool;
+use crate::blockchain::merkle::{merkle_branch, TransactionProof};
+use crate::blockchain::orphan_pool::OrphanPool;
+use crate::blockchain::pow::{bits_from_target, target_from_bits, work_from_bits, U256, POW_LIMIT_BITS};
+use crate::chain_spec::{Allocation, ChainSpec, Checkpoint};
+use crate::errors::{BlockError, ChainSpecError, StorageError, TransactionError};
 use crate::wallet::wallet::Wallet;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Moedas criadas pela coinbase de cada bloco minerado.
+pub const BLOCK_SUBSIDY: u64 = 50;
+
+/// O target é recalculado a cada RETARGET_INTERVAL blocos.
+pub const RETARGET_INTERVAL: u64 = 10;
+/// Tempo desejado entre blocos, em segundos.
+pub const TARGET_BLOCK_TIME: i64 = 10;
+/// Cada retarget muda o target no máximo por este fator (para cima ou para baixo).
+pub const MAX_RETARGET_FACTOR: i64 = 4;
+/// O timestamp precisa ser maior que a mediana destes últimos blocos.
+pub const MEDIAN_TIME_SPAN: usize = 11;
+/// Quanto um bloco pode estar adiantado em relação ao relógio local, em segundos.
+pub const MAX_FUTURE_BLOCK_TIME: i64 = 2 * 60 * 60;
+/// Um bloco de ramo lateral só é guardado se o ramo tiver pelo menos o trabalho
+/// da chain ativa de MAX_SIDE_BRANCH_DEPTH blocos atrás do topo.
+pub const MAX_SIDE_BRANCH_DEPTH: usize = 100;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(try_from = "SerializedBlockchain")]
 pub struct Blockchain {
     pub blocks: Vec<Block>,
-    pub pending_transactions: Vec<Transaction>,
-    pub difficulty: u32,
+    pub mempool: Mempool,
+    // target compacto do gênese, ponto de partida do retarget
+    pub initial_bits: u32,
+    // hashes conhecidos por altura, vindos da chain spec
+    #[serde(default)]
+    pub checkpoints: Vec<Checkpoint>,
+    // onde está cada transação confirmada na chain ativa, por tx_hash
+    #[serde(skip)]
+    pub tx_map: TxIndex,
+    #[serde(skip)]
+    pub ledger: Ledger,
+    // todos os blocos conhecidos por hash, inclusive os de ramos laterais
     #[serde(skip)]
-    pub tx_map: HashMap<String, Transaction>,
+    pub index: BlockIndex,
+    // blocos recebidos antes do pai
+    #[serde(skip)]
+    pub orphans: OrphanPool,
+    // se presente, todo bloco indexado é gravado em disco antes; clones compartilham o store
+    #[serde(skip)]
+    pub store: Option<Arc<Mutex<BlockStore>>>,
+}
+
+// o que vem no JSON; ledger, índice de blocos e de transações são refeitos a
+// partir dos blocos, senão a chain desserializada voltaria sem saldos nem provas
+#[derive(Deserialize)]
+struct SerializedBlockchain {
+    blocks: Vec<Block>,
+    mempool: Mempool,
+    initial_bits: u32,
+    #[serde(default)]
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl TryFrom<SerializedBlockchain> for Blockchain {
+    type Error = String;
+
+    fn try_from(data: SerializedBlockchain) -> Result<Self, Self::Error> {
+        let ledger = Ledger::from_blocks(&data.blocks).map_err(|e| e.to_string())?;
+        let mut index = BlockIndex::new();
+        for block in &data.blocks {
+            if !index.insert(block.clone()) {
+                return Err(format!("Block {} does not extend the previous one", block.hash));
+            }
+        }
+        let mut blockchain = Blockchain {
+            blocks: data.blocks,
+            mempool: data.mempool,
+            initial_bits: data.initial_bits,
+            checkpoints: data.checkpoints,
+            tx_map: HashMap::new(),
+            ledger,
+            index,
+            orphans: OrphanPool::default(),
+            store: None,
+        };
+        blockchain.rebuild_tx_index();
+        Ok(blockchain)
+    }
+}
+
+/// Posição de uma transação confirmada: bloco, altura e índice dentro do bloco.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxLocation {
+    pub block_hash: String,
+    pub height: u64,
+    pub position: usize,
+}
+
+/// Índice das transações confirmadas: tx_hash -> posição na chain ativa.
+pub type TxIndex = HashMap<String, TxLocation>;
+
+/// O que aconteceu com um bloco recebido da rede.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BlockStatus {
+    /// Estendeu o topo (junto com os órfãos que esperavam por ele).
+    Connected,
+    /// Completou um ramo com mais trabalho e a chain ativa foi trocada.
+    Reorganized,
+    /// Ficou guardado no índice, num ramo lateral com menos trabalho.
+    SideBranch,
+    /// Pai desconhecido: ficou no pool de órfãos esperando `missing_parent`.
+    Orphaned { missing_parent: String },
+    AlreadyKnown,
 }
 
 impl Blockchain {
     pub fn new() -> Self {
+        Blockchain::with_genesis_allocations(&[])
+    }
+
+    //genesis block credits each (address, amount) pair
+    pub fn with_genesis_allocations(allocations: &[(String, u64)]) -> Self {
+        let spec = ChainSpec {
+            allocations: allocations
+                .iter()
+                .map(|(address, amount)| Allocation { address: address.clone(), amount: *amount })
+                .collect(),
+            ..ChainSpec::default()
+        };
+        Blockchain::from_spec(&spec).expect("Alocações do gênese inválidas")
+    }
+
+    /// Chain só com o gênese definido pela spec. Falha se o hash não bater com o
+    /// `genesis_hash` da spec ou se as alocações não couberem no ledger.
+    pub fn from_spec(spec: &ChainSpec) -> Result<Self, ChainSpecError> {
+        spec.check()?;
+        let genesis = spec.genesis_block();
+        spec.check_genesis(&genesis)?;
+
         let mut blockchain = Blockchain {
             blocks: Vec::new(),
-            pending_transactions: Vec::new(),
-            difficulty: 3,
+            mempool: Mempool::default(),
+            initial_bits: spec.initial_bits,
+            checkpoints: spec.checkpoints.clone(),
             tx_map: HashMap::new(),
+            ledger: Ledger::new(),
+            index: BlockIndex::new(),
+            orphans: OrphanPool::default(),
+            store: None,
         };
-        let genesis = Block::new(0, vec![], "0".to_string());
+        blockchain
+            .ledger
+            .apply_block(&genesis)
+            .map_err(|e| ChainSpecError::InvalidAllocations(e.to_string()))?;
+        index_transactions(&mut blockchain.tx_map, &genesis);
+        blockchain.index.insert(genesis.clone());
         blockchain.blocks.push(genesis);
-        blockchain 
+        Ok(blockchain)
     }
 
-    pub fn add_transaction_to_mempool(&mut self, tx: Transaction) {
-        let txid = tx.tx_hash();
-        if tx.is_valid() {
-            self.tx_map.insert(txid, tx.clone());
-            self.pending_transactions.push(tx);
-        } else {
+    pub fn balance_of(&self, address: &str) -> u64 {
+        self.ledger.balance_of(address)
+    }
+
+    /// Nonce da próxima transação de `address`: o do ledger mais as que já estão no mempool.
+    pub fn next_nonce(&self, address: &str) -> u64 {
+        let pending = self.mempool.iter().filter(|tx| tx.from_address == address).count();
+        self.ledger.next_nonce(address) + pending as u64
+    }
+
+    //soma do que o endereço já gastou em transações pendentes
+    fn pending_spent(&self, address: &str) -> u64 {
+        self.mempool
+            .iter()
+            .filter(|tx| tx.from_address == address)
+            .fold(0u64, |acc, tx| acc.saturating_add(tx.total_cost().unwrap_or(u64::MAX)))
+    }
+
+    pub fn add_transaction_to_mempool(&mut self, tx: Transaction) -> Result<(), TransactionError> {
+        let now = Utc::now().timestamp();
+        let expired = self.mempool.expire(now);
+        for tx in &expired {
+            println!("Transação expirou no mempool: {}", tx.tx_hash());
+        }
+        self.journal_mempool(None, &expired);
+
+        if let Err(e) = tx.check_address_binding() {
+            println!("Transaction rejected: {}", e);
+            return Err(e);
+        }
+        if !tx.is_valid() {
             println!("Invalid Transaction, ignoring...");
+            return Err(TransactionError::InvalidSignature("Signature does not match".to_string()));
+        }
+
+        // o próximo nonce já conta as pendentes do mesmo remetente
+        let expected = self.next_nonce(&tx.from_address);
+        if tx.nonce != expected {
+            let e = TransactionError::InvalidNonce { address: tx.from_address.clone(), expected, got: tx.nonce };
+            println!("Transaction rejected: {}", e);
+            return Err(e);
         }
+
+        let cost = tx.total_cost().ok_or(TransactionError::InvalidAmount)?;
+        let spent = self.pending_spent(&tx.from_address).saturating_add(cost);
+        if let Err(e) = self.ledger.check_funds(&tx.from_address, spent) {
+            println!("Transaction rejected: {}", e);
+            return Err(e);
+        }
+
+        let tx_hash = tx.tx_hash();
+        let evicted = self.mempool.insert(tx, now).inspect_err(|e| println!("Transaction rejected: {}", e))?;
+        for tx in &evicted {
+            println!("Mempool cheio, despejando transação: {}", tx.tx_hash());
+        }
+        self.journal_mempool(self.mempool.get(&tx_hash), &evicted);
+        Ok(())
     }
 
-    pub fn add_block(&mut self) {
+    /// Monta o próximo bloco (ainda sem PoW): as pendentes de maior fee rate que
+    /// cabem em MAX_BLOCK_SIZE/MAX_BLOCK_TRANSACTIONS e uma coinbase para
+    /// reward_address com subsídio + taxas. Não mexe no mempool.
+    pub fn create_block_template(&self, reward_address: &str) -> Block {
         let index = self.blocks.len() as u64;
         let previous_hash = if let Some(last_block) = self.blocks.last() {
             last_block.hash.clone()
         } else {
             "0".to_string()
         };
-        
-        println!("Pending transactions antes de criar bloco: {:?}", self.pending_transactions);
-        let txs = self.pending_transactions.clone();
-        self.pending_transactions.clear();
-        println!("Transações a serem incluídas no bloco: {:?}", txs);
-        
-        let mut new_block = Block::new(index, txs, previous_hash);
+
+        // o tamanho da coinbase não depende do valor (u64 de largura fixa)
+        let coinbase_size = Transaction::new_coinbase(reward_address.to_string(), 0, index).size();
+        let (selected, reward) = self.select_transactions(
+            MAX_BLOCK_SIZE.saturating_sub(coinbase_size),
+            MAX_BLOCK_TRANSACTIONS - 1,
+        );
+        let mut txs = vec![Transaction::new_coinbase(reward_address.to_string(), reward, index)];
+        txs.extend(selected);
+
+        let mut template = Block::new(index, txs, previous_hash, self.required_bits(index));
+        // relógio local atrasado não pode gerar bloco inválido
+        let min_timestamp = median_time_past(&self.blocks).saturating_add(1);
+        if template.timestamp < min_timestamp {
+            template.timestamp = min_timestamp;
+            template.hash = template.header_hash();
+        }
+        template
+    }
+
+    /// Escolha gulosa por fee rate respeitando a ordem de nonce de cada remetente:
+    /// a cada passo entra a transação de maior fee rate entre as primeiras de cada
+    /// remetente. Se uma não cabe ou não vale, as seguintes do mesmo remetente também saem.
+    /// Devolve as escolhidas e a recompensa da coinbase (subsídio + taxas).
+    fn select_transactions(&self, max_size: usize, max_count: usize) -> (Vec<Transaction>, u64) {
+        // cada pendente vai com o tamanho que o mempool já guardou
+        let mut queues: Vec<VecDeque<(&Transaction, usize)>> = Vec::new();
+        let mut queue_of: HashMap<&str, usize> = HashMap::new();
+        for (tx, tx_size) in self.mempool.iter_with_sizes() {
+            let i = *queue_of.entry(tx.from_address.as_str()).or_insert_with(|| {
+                queues.push(VecDeque::new());
+                queues.len() - 1
+            });
+            queues[i].push_back((tx, tx_size));
+        }
+
+        let mut scratch = self.ledger.clone();
+        let mut selected = Vec::new();
+        let mut size = 0usize;
+        // soma já com o subsídio: uma taxa que estouraria a coinbase fica de fora
+        let mut reward = BLOCK_SUBSIDY;
+        while selected.len() < max_count {
+            let best = queues
+                .iter()
+                .enumerate()
+                .filter_map(|(i, queue)| queue.front().map(|&(tx, tx_size)| (i, tx, tx_size)))
+                .reduce(|best, candidate| {
+                    // empate fica com quem chegou primeiro
+                    let ordering = cmp_fee_rates((candidate.1.fee, candidate.2), (best.1.fee, best.2));
+                    if ordering.is_gt() { candidate } else { best }
+                });
+            let Some((i, tx, tx_size)) = best else { break };
+            queues[i].pop_front();
+
+            let fits = size + tx_size <= max_size;
+            match (fits, reward.checked_add(tx.fee)) {
+                (true, Some(total)) => match scratch.apply_transaction(tx) {
+                    Ok(()) => {
+                        size += tx_size;
+                        reward = total;
+                        selected.push(tx.clone());
+                        continue;
+                    }
+                    Err(e) => println!("Deixando transação fora do bloco: {}", e),
+                },
+                _ => println!("Transação {} não cabe no bloco", tx.tx_hash()),
+            }
+            // sem ela, as próximas do mesmo remetente ficam com nonce pulado
+            queues[i].clear();
+        }
+        (selected, reward)
+    }
+
+    /// Pendentes em ordem decrescente de fee rate (empates por chegada).
+    pub fn pending_by_fee_rate(&self) -> Vec<Transaction> {
+        let mut pending: Vec<(&Transaction, usize)> = self.mempool.iter_with_sizes().collect();
+        pending.sort_by(|a, b| cmp_fee_rates((b.0.fee, b.1), (a.0.fee, a.1)));
+        pending.into_iter().map(|(tx, _)| tx.clone()).collect()
+    }
+
+    //minera as transações pendentes e paga a recompensa para reward_address
+    pub fn add_block(&mut self, reward_address: &str) {
+        println!("Pending transactions antes de criar bloco: {:?}", self.mempool.transactions());
+        let mut new_block = self.create_block_template(reward_address);
         println!("Bloco criado com transações: {:?}", new_block.transactions);
         
-        new_block.mine_block(self.difficulty);
+        new_block.mine_block();
         println!("Bloco minerado com transações: {:?}", new_block.transactions);
         
-        if !new_block.is_valid(self.difficulty) {
-            println!("Bloco inválido (hash inconsistente ou proof-of-work falhou). Não adicionando!");
+        match self.connect_block(new_block) {
+            Ok(()) => println!("Bloco adicionado à blockchain: {:?}", self.blocks.last().unwrap()),
+            Err(e) => println!("Bloco inválido ({}). Não adicionando!", e),
+        }
+    }
+
+    /// Valida um bloco que deveria estender o topo atual e, se estiver tudo certo,
+    /// conecta: atualiza o ledger e tira do mempool o que foi confirmado.
+    pub fn connect_block(&mut self, block: Block) -> Result<(), BlockError> {
+        self.check_checkpoint(&block)?;
+        let now = Utc::now().timestamp();
+        // valida numa cópia: se a gravação falhar o ledger fica como estava
+        let mut ledger = self.ledger.clone();
+        validate_block(&self.blocks, &mut ledger, &block, self.initial_bits, now, true)?;
+        self.index_block(block.clone())?;
+        self.ledger = ledger;
+
+        index_transactions(&mut self.tx_map, &block);
+        self.blocks.push(block);
+        self.save_tx_index_update(&[], &self.blocks[self.blocks.len() - 1..]);
+        let removed = self.purge_mempool(self.blocks.len() - 1);
+        self.journal_mempool(None, &removed);
+        Ok(())
+    }
+
+    // remove do mempool o que foi confirmado a partir de `from_height` e o que o
+    // novo ledger não aceita mais; devolve o que saiu
+    fn purge_mempool(&mut self, from_height: usize) -> Vec<Transaction> {
+        let confirmed: HashSet<String> = self.blocks[from_height.min(self.blocks.len())..]
+            .iter()
+            .flat_map(|block| block.transactions.iter().map(|tx| tx.tx_hash()))
+            .collect();
+
+        let mut scratch = self.ledger.clone();
+        let mut removed = self.mempool.retain(|tx| {
+            if confirmed.contains(&tx.tx_hash()) {
+                return false;
+            }
+            match scratch.apply_transaction(tx) {
+                Ok(()) => true,
+                Err(e) => {
+                    println!("Descartando transação do mempool: {}", e);
+                    false
+                }
+            }
+        });
+        for expired in self.mempool.expire(Utc::now().timestamp()) {
+            println!("Transação expirou no mempool: {}", expired.tx_hash());
+            removed.push(expired);
+        }
+        removed
+    }
+
+    // indexa o bloco; com store, só depois de gravado em disco
+    fn index_block(&mut self, block: Block) -> Result<(), BlockError> {
+        if self.index.contains(&block.hash) {
+            return Ok(());
+        }
+        if let Some(store) = &self.store {
+            store
+                .lock()
+                .unwrap()
+                .append(&block)
+                .map_err(|e| BlockError::Storage(e.to_string()))?;
+        }
+        self.index.insert(block);
+        Ok(())
+    }
+
+    // regrava o índice de transações inteiro
+    fn save_tx_index(&self) {
+        let (Some(store), Some(tip)) = (&self.store, self.blocks.last()) else {
             return;
+        };
+        if let Err(e) = store.lock().unwrap().save_tx_index(&tip.hash, &self.tx_map) {
+            println!("Não foi possível salvar o índice de transações: {}", e);
         }
-        if new_block.previous_hash != self.blocks.last().unwrap().hash {
-            println!("Bloco inválido (previous_hash difere do hash do último bloco). Não adicionando!");
+    }
+
+    // grava só o que mudou no índice de transações: sai o que estava nos blocos
+    // desconectados, entra o que está nos conectados. Depois de atualizações
+    // demais regrava o índice inteiro
+    fn save_tx_index_update(&self, disconnected: &[Block], connected: &[Block]) {
+        let (Some(store), Some(tip)) = (&self.store, self.blocks.last()) else {
+            return;
+        };
+        if store.lock().unwrap().tx_index_is_stale() {
+            self.save_tx_index();
             return;
         }
-        
-        self.blocks.push(new_block);
-        println!("Bloco adicionado à blockchain: {:?}", self.blocks.last().unwrap());
+        let mut added = TxIndex::new();
+        for block in connected {
+            index_transactions(&mut added, block);
+        }
+        let update = TxIndexUpdate {
+            tip: tip.hash.clone(),
+            disconnected: disconnected.iter().map(|block| block.hash.clone()).collect(),
+            added,
+        };
+        if let Err(e) = store.lock().unwrap().append_tx_index(&update) {
+            println!("Não foi possível salvar o índice de transações: {}", e);
+        }
+    }
+
+    /// Refaz o índice de transações a partir da chain ativa.
+    pub fn rebuild_tx_index(&mut self) {
+        self.tx_map.clear();
+        for block in &self.blocks {
+            index_transactions(&mut self.tx_map, block);
+        }
+    }
+
+    // regrava o journal do mempool só com as pendentes atuais
+    fn save_mempool(&self) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.lock().unwrap().save_mempool(&self.mempool.transactions()) {
+                println!("Não foi possível salvar o mempool: {}", e);
+            }
+        }
+    }
+
+    // registra no journal o que entrou e saiu do mempool; quando o journal já é
+    // quase todo lixo, regrava só as pendentes
+    fn journal_mempool(&self, added: Option<&Transaction>, removed: &[Transaction]) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let mut store = store.lock().unwrap();
+        let result = if store.mempool_journal_is_stale(self.mempool.len()) {
+            store.save_mempool(&self.mempool.transactions())
+        } else {
+            let removed: Vec<String> = removed.iter().map(|tx| tx.tx_hash()).collect();
+            store.append_mempool(added.map(std::slice::from_ref).unwrap_or_default(), &removed)
+        };
+        if let Err(e) = result {
+            println!("Não foi possível salvar o mempool: {}", e);
+        }
+    }
+
+    /// Liga a chain ao store em `data_dir`: reindexa os blocos gravados, revalida
+    /// tudo a partir do gênese ativando a ponta com mais trabalho e devolve ao
+    /// mempool as pendentes salvas. O índice de transações gravado é usado se
+    /// terminar no mesmo topo; senão é refeito. Daqui em diante todo bloco aceito
+    /// é gravado.
+    pub fn attach_store(&mut self, data_dir: &Path) -> Result<(), StorageError> {
+        let (mut store, blocks) = BlockStore::open(data_dir)?;
+        let pending = store.load_mempool()?;
+        let saved_tx_index = store.load_tx_index()?;
+
+        let stored = blocks.len();
+        let mut unknown = 0;
+        for block in blocks {
+            // gravados em ordem de chegada, então o pai sempre vem antes
+            if !self.index.contains(&block.previous_hash) || !self.index.insert(block) {
+                unknown += 1;
+            }
+        }
+        if unknown > 0 {
+            println!("{} bloco(s) gravado(s) não ligam no nosso gênese, ignorando", unknown);
+        }
+        self.store = Some(Arc::new(Mutex::new(store)));
+
+        if let Err(e) = self.activate_best_tip_with(false) {
+            println!("Blocos gravados inválidos descartados: {}", e);
+        }
+        let tip = self.blocks.last().map(|b| b.hash.clone()).unwrap_or_default();
+        match saved_tx_index {
+            Some((saved_tip, transactions)) if saved_tip == tip => {
+                self.tx_map = transactions;
+                if self.store.as_ref().unwrap().lock().unwrap().tx_index_is_stale() {
+                    self.save_tx_index();
+                }
+            }
+            _ => {
+                println!("Índice de transações gravado não termina no topo atual, refazendo");
+                self.rebuild_tx_index();
+                self.save_tx_index();
+            }
+        }
+        println!(
+            "{} bloco(s) carregado(s) de {}, altura {}",
+            stored,
+            data_dir.display(),
+            self.blocks.len() - 1
+        );
+
+        // as pendentes voltam sem passar pelo journal, que depois é regravado só
+        // com as que a chain recarregada ainda aceita
+        let store = self.store.take();
+        for tx in pending {
+            if let Err(e) = self.add_transaction_to_mempool(tx) {
+                println!("Transação salva descartada: {}", e);
+            }
+        }
+        self.store = store;
+        self.save_mempool();
+        Ok(())
     }
     
 
+    /// Target (compacto) exigido para o bloco de altura `height`, calculado só a
+    /// partir dos blocos anteriores a ele, então todo nó chega no mesmo valor.
+    pub fn required_bits(&self, height: u64) -> u32 {
+        let end = (height as usize).min(self.blocks.len());
+        next_bits(&self.blocks[..end], self.initial_bits)
+    }
+
     //check if entire blockchain is valid
     pub fn is_valid(&self) -> bool {
+        self.validate().is_ok()
+    }
+
+    /// Revalida a chain inteira a partir do gênese, bloco a bloco, com as mesmas
+    /// regras de connect_block. Devolve o ledger resultante.
+    pub fn validate(&self) -> Result<Ledger, BlockError> {
+        let Some((genesis, _)) = self.blocks.split_first() else {
+            return Ok(Ledger::new());
+        };
+        let now = Utc::now().timestamp();
+        let trusted = self.assumed_valid_len(&self.blocks);
+        let mut ledger = Ledger::from_blocks(std::slice::from_ref(genesis))?;
         for i in 1..self.blocks.len() {
-            let current = &self.blocks[i];
-            let previous = &self.blocks[i - 1];
+            self.check_checkpoint(&self.blocks[i])?;
+            validate_block(&self.blocks[..i], &mut ledger, &self.blocks[i], self.initial_bits, now, i >= trusted)?;
+        }
+        Ok(ledger)
+    }
 
-            // Se o previous_hash não bate com o hash do bloco anterior
-            if current.previous_hash != previous.hash {
-                return false;
-            }
+    /// Um bloco na altura de um checkpoint precisa ter exatamente o hash dele.
+    pub fn check_checkpoint(&self, block: &Block) -> Result<(), BlockError> {
+        match self.checkpoints.iter().find(|cp| cp.height == block.index) {
+            Some(cp) if cp.hash != block.hash => Err(BlockError::CheckpointMismatch {
+                height: cp.height,
+                expected: cp.hash.clone(),
+                got: block.hash.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
 
-            // Recalcular e comparar com o hash guardado
-            let recalculated = calculate_hash(
-                current.index,
-                current.timestamp,
-                &current.transactions,
-                &current.previous_hash,
-                current.nonce
-            );
-            if recalculated != current.hash {
-                return false;
-            }
-            // (Opcional) check PoW se quiser
-            // if !current.hash_starts_with_zeros(self.difficulty) { return false; }
+    /// Altura do checkpoint mais alto que a chain ativa já alcançou: nada abaixo
+    /// dele pode ser desfeito por um reorg.
+    pub fn last_checkpoint_height(&self) -> Option<u64> {
+        self.checkpoints
+            .iter()
+            .map(|cp| cp.height)
+            .filter(|&height| (height as usize) < self.blocks.len())
+            .max()
+    }
+
+    // quantos blocos do começo de `blocks` estão amarrados a um checkpoint (o hash
+    // do checkpoint fixa todos os ancestrais) e podem pular as assinaturas
+    fn assumed_valid_len(&self, blocks: &[Block]) -> usize {
+        self.checkpoints
+            .iter()
+            .filter(|cp| blocks.get(cp.height as usize).is_some_and(|block| block.hash == cp.hash))
+            .map(|cp| cp.height as usize + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Trabalho acumulado da chain: soma do trabalho de cada bloco, pelo target gravado.
+    /// Vem do índice quando o topo está nele; só uma chain montada por fora, com
+    /// `blocks` trocado à mão, é somada bloco a bloco.
+    pub fn chain_work(&self) -> U256 {
+        let tip = self.blocks.last().map(|block| block.hash.as_str()).unwrap_or_default();
+        match self.index.get(tip) {
+            Some(entry) => entry.chain_work,
+            None => cumulative_work(&self.blocks),
         }
+    }
 
-        true
+    /// Fork choice: vence a chain com mais trabalho acumulado, não a mais longa.
+    #[allow(dead_code)]
+    pub fn has_more_work_than(&self, other: &Blockchain) -> bool {
+        self.chain_work() > other.chain_work()
     }
 
-    // se existir chain maior e válida, substitui
-    pub fn replace_chain_if_longer(&mut self, new_chain: &Blockchain) -> bool {
-        if !new_chain.is_valid() {
+    // se os blocos recebidos somam mais trabalho e são válidos, faz o reorg para eles
+    pub fn replace_chain_if_heavier(&mut self, new_blocks: &[Block]) -> bool {
+        if cumulative_work(new_blocks) <= self.chain_work() {
             return false;
         }
+        match self.reorganize(new_blocks) {
+            Ok(()) => true,
+            Err(e) => {
+                println!("Chain recebida é inválida: {}", e);
+                false
+            }
+        }
+    }
 
-        if new_chain.blocks.len() > self.blocks.len() {
-            self.blocks = new_chain.blocks.clone();
-            // se quiser, também pegar new_chain.pending_transactions
-            true
-        } else {
-            false
+    /// Troca a chain ativa por `new_blocks`: acha o ponto de fork, desconecta os
+    /// blocos locais até ele, conecta os novos com a validação completa e devolve
+    /// ao mempool as transações que só existiam nos blocos desconectados.
+    /// Se algum bloco novo for inválido nada muda.
+    pub fn reorganize(&mut self, new_blocks: &[Block]) -> Result<(), BlockError> {
+        self.reorganize_with(new_blocks, true)
+    }
+
+    // com `index_txs` falso o índice de transações não é tocado (o attach_store
+    // troca o índice inteiro depois)
+    fn reorganize_with(&mut self, new_blocks: &[Block], index_txs: bool) -> Result<(), BlockError> {
+        let fork = fork_point(&self.blocks, new_blocks);
+        if fork == 0 {
+            return Err(BlockError::DifferentGenesis);
+        }
+        if let Some(checkpoint_height) = self.last_checkpoint_height() {
+            if fork as u64 <= checkpoint_height {
+                return Err(BlockError::ReorgBelowCheckpoint { fork_height: fork as u64, checkpoint_height });
+            }
         }
+
+        // desfaz os blocos locais até o fork e conecta os novos em cima
+        let now = Utc::now().timestamp();
+        let trusted = self.assumed_valid_len(new_blocks);
+        let mut ledger = self.ledger.clone();
+        for block in self.blocks[fork..].iter().rev() {
+            ledger.undo_block(block)?;
+        }
+        let mut blocks = self.blocks[..fork].to_vec();
+        for block in &new_blocks[fork..] {
+            self.check_checkpoint(block)?;
+            validate_block(&blocks, &mut ledger, block, self.initial_bits, now, blocks.len() >= trusted)?;
+            blocks.push(block.clone());
+        }
+
+        for block in &blocks[fork..] {
+            self.index_block(block.clone())?;
+        }
+        let disconnected = std::mem::replace(&mut self.blocks, blocks).split_off(fork);
+        self.ledger = ledger;
+        println!(
+            "Reorg: {} bloco(s) desconectado(s), {} conectado(s) a partir da altura {}",
+            disconnected.len(),
+            self.blocks.len() - fork,
+            fork
+        );
+
+        let mut orphaned = Vec::new();
+        for block in &disconnected {
+            orphaned.extend(block.transactions.iter().filter(|tx| !tx.is_coinbase()).cloned());
+        }
+        if index_txs {
+            for block in &disconnected {
+                unindex_transactions(&mut self.tx_map, block);
+            }
+            for block in &self.blocks[fork..] {
+                index_transactions(&mut self.tx_map, block);
+            }
+            self.save_tx_index_update(&disconnected, &self.blocks[fork..]);
+        }
+
+        for tx in self.mempool.readd_front(orphaned, now) {
+            println!("Mempool cheio, despejando transação: {}", tx.tx_hash());
+        }
+        self.purge_mempool(fork);
+        // as devolvidas entram na frente; o journal só acrescenta no fim, então regrava
+        self.save_mempool();
+        Ok(())
     }
 
     //cria uma transação assinada e põe no mempool
@@ -122,70 +696,373 @@ impl Blockchain {
         to_address: String,
         amount: u64
     ) {
-        let tx = Transaction::new_signed(from_wallet, to_address, amount);
+        let nonce = self.next_nonce(&from_wallet.address);
+        let tx = Transaction::new_signed(from_wallet, to_address, amount, nonce);
         if let Ok(tx_ok) = tx {
-            self.add_transaction_to_mempool(tx_ok);
+            if let Err(e) = self.add_transaction_to_mempool(tx_ok) {
+                println!("Failed to add transaction to mempool: {}", e);
+            }
         } else {
             println!("Failed to create signed transaction");
         }
     }
 
     //recebe um bloco da rede
-    pub fn add_block_from_network(&mut self, block: Block) {
-        // se block.index == self.blocks.len(), é o próximo bloco
-        if block.index as usize == self.blocks.len() {
-            let last_hash = self.blocks.last().unwrap().hash.clone();
-            if block.previous_hash == last_hash {
-                // (Opcional) Verificar se PoW é válido
-                // ex.: if !block.is_valid_pow(self.difficulty) { return; }
+    pub fn add_block_from_network(&mut self, block: Block) -> Result<BlockStatus, BlockError> {
+        if self.index.contains(&block.hash) || self.orphans.contains(&block.hash) {
+            return Ok(BlockStatus::AlreadyKnown);
+        }
+
+        let tip = self.blocks.last().map(|b| b.hash.clone()).unwrap_or_default();
+        if block.previous_hash == tip {
+            self.connect_block(block)?;
+            let tip = self.blocks.last().unwrap().hash.clone();
+            // órfãos que esperavam este bloco; um filho inválido não desfaz o bloco já conectado
+            self.adopt_orphans(&tip);
+            if let Err(e) = self.activate_best_tip() {
+                println!("Órfão descartado: {}", e);
+            }
+            return Ok(BlockStatus::Connected);
+        }
+
+        // pai desconhecido ou ramo lateral: só guarda depois de checar o PoW e os
+        // checkpoints, para não encher o pool/índice de lixo de graça. O check também
+        // recusa transações repetidas: um gêmeo adulterado tem o mesmo hash e, guardado
+        // antes, faria o bloco verdadeiro voltar como AlreadyKnown
+        block.check()?;
+        self.check_checkpoint(&block)?;
+        if let Some(checkpoint_height) = self.last_checkpoint_height() {
+            if block.index <= checkpoint_height {
+                return Err(BlockError::ReorgBelowCheckpoint { fork_height: block.index, checkpoint_height });
+            }
+        }
+        let parent_hash = if self.index.contains(&block.previous_hash) {
+            let parent_hash = block.previous_hash.clone();
+            self.check_side_block(&block)?;
+            self.index_block(block)?;
+            parent_hash
+        } else {
+            let hash = block.hash.clone();
+            for evicted in self.orphans.insert(block) {
+                println!("Pool de órfãos cheio, descartando bloco {}", evicted.hash);
+            }
+            let root_parent = self.orphans.root_of(&hash).unwrap().previous_hash.clone();
+            if !self.index.contains(&root_parent) {
+                return Ok(BlockStatus::Orphaned { missing_parent: root_parent });
+            }
+            root_parent
+        };
+
+        self.adopt_orphans(&parent_hash);
+        let old_tip = tip;
+        if !self.activate_best_tip()? {
+            Ok(BlockStatus::SideBranch)
+        } else if self.height_of(&old_tip).is_some() {
+            Ok(BlockStatus::Connected)
+        } else {
+            Ok(BlockStatus::Reorganized)
+        }
+    }
+
+    // passa para o índice os órfãos que descendem de `parent_hash` (já indexado)
+    fn adopt_orphans(&mut self, parent_hash: &str) {
+        let mut parents = vec![parent_hash.to_string()];
+        while let Some(parent) = parents.pop() {
+            for child in self.orphans.take_children(&parent) {
+                let hash = child.hash.clone();
+                match self.check_side_block(&child).and_then(|()| self.index_block(child)) {
+                    Ok(()) => parents.push(hash),
+                    Err(e) => println!("Órfão {} descartado: {}", hash, e),
+                }
+            }
+        }
+    }
 
-                // se tudo ok, adiciona
-                self.blocks.push(block);
+    // regras que dá para checar num bloco de ramo lateral sem o ledger do ramo:
+    // altura, bits e timestamp em relação aos ancestrais dele, e se o ramo ainda
+    // tem chance de alcançar a chain ativa. Sem isso qualquer um encheria o
+    // índice (e o disco) com blocos fáceis pendurados no gênese.
+    fn check_side_block(&self, block: &Block) -> Result<(), BlockError> {
+        let parent = self.index.get(&block.previous_hash).ok_or(BlockError::WrongPreviousHash)?;
+        let height = parent.height + 1;
+        if block.index != height {
+            return Err(BlockError::NotNextBlock { expected: height, got: block.index });
+        }
+
+        let recent = self
+            .index
+            .recent_blocks(&block.previous_hash, (RETARGET_INTERVAL as usize).max(MEDIAN_TIME_SPAN));
+        let required = next_bits_at(height, &recent, self.initial_bits);
+        if block.bits != required {
+            return Err(BlockError::UnexpectedBits { expected: required, got: block.bits });
+        }
+        check_timestamp(&recent, block, Utc::now().timestamp())?;
+
+        let branch_work = parent.chain_work.saturating_add(work_from_bits(block.bits));
+        let floor = self
+            .blocks
+            .len()
+            .checked_sub(MAX_SIDE_BRANCH_DEPTH + 1)
+            .and_then(|i| self.index.get(&self.blocks[i].hash));
+        if floor.is_some_and(|entry| branch_work < entry.chain_work) {
+            return Err(BlockError::BranchTooFarBehind { height });
+        }
+        Ok(())
+    }
+
+    /// Se alguma ponta do índice tiver mais trabalho que a chain ativa, troca para
+    /// ela. Pontas que não passam na validação saem do índice e a próxima é tentada.
+    /// Devolve se a chain ativa mudou; o erro só volta se nenhuma troca deu certo.
+    pub fn activate_best_tip(&mut self) -> Result<bool, BlockError> {
+        self.activate_best_tip_with(true)
+    }
+
+    fn activate_best_tip_with(&mut self, index_txs: bool) -> Result<bool, BlockError> {
+        let mut last_error = None;
+        while let Some(best) = self.index.best_tip() {
+            if best.chain_work <= self.chain_work() {
+                break;
+            }
+            let branch = self.index.branch_to(&best.hash);
+            match self.reorganize_with(&branch, index_txs) {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    // não sabemos qual bloco do ramo falhou: tira a ponta e tenta de novo;
+                    // se o inválido estiver no meio, ele vira ponta e sai na próxima volta
+                    println!("Ponta {} rejeitada: {}", best.hash, e);
+                    self.index.remove_tip(&best.hash);
+                    last_error = Some(e);
+                }
             }
-        } else if block.index as usize > self.blocks.len() {
-            // Precisamos da cadeia do outro pra ver se é maior
-            println!("A new block has bigger index, we might request the full chain from that node!");
         }
+        match last_error {
+            Some(e) => Err(e),
+            None => Ok(false),
+        }
+    }
+
+    /// Pontas de todos os ramos conhecidos, da com mais trabalho para a com menos.
+    pub fn tips(&self) -> Vec<Tip> {
+        self.index.tips()
+    }
+
+    /// Altura do bloco `hash` na chain ativa.
+    pub fn height_of(&self, hash: &str) -> Option<usize> {
+        self.blocks.iter().position(|block| block.hash == hash)
     }
 
+    /// Hashes de todos os blocos gravados nessa altura, da chain ativa e de ramos
+    /// laterais. Vazio quando a chain não tem store.
+    pub fn stored_hashes_at(&self, height: u64) -> Vec<String> {
+        self.store
+            .as_ref()
+            .map(|store| store.lock().unwrap().hashes_at(height).to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Bloco conhecido com esse hash, da chain ativa ou de um ramo lateral.
+    pub fn get_block(&self, hash: &str) -> Option<&Block> {
+        self.index.get(hash).map(|entry| &entry.block)
+    }
+
+    /// Prova de inclusão de uma transação já confirmada na chain.
+    /// Retorna também a altura do bloco onde ela está.
+    pub fn transaction_proof(&self, tx_hash: &str) -> Option<(TransactionProof, u64)> {
+        let location = self.tx_map.get(tx_hash)?;
+        let block = self.block_at(location)?;
+        let hashes: Vec<String> = block.transactions.iter().map(|tx| tx.tx_hash()).collect();
+        let branch = merkle_branch(&hashes, location.position)?;
+        let proof = TransactionProof {
+            tx_hash: tx_hash.to_string(),
+            position: location.position,
+            leaf_count: hashes.len(),
+            branch,
+            header: block.header(),
+        };
+        Some((proof, block.index))
+    }
+
+    /// Onde a transação foi confirmada na chain ativa.
+    pub fn transaction_location(&self, tx_hash: &str) -> Option<&TxLocation> {
+        self.tx_map.get(tx_hash)
+    }
+
+    // bloco da chain ativa apontado por `location`, se ele ainda for o mesmo
+    fn block_at(&self, location: &TxLocation) -> Option<&Block> {
+        self.blocks
+            .get(location.height as usize)
+            .filter(|block| block.hash == location.block_hash)
+    }
+
+    /// Transação pendente no mempool ou confirmada na chain ativa.
     #[allow(dead_code)]
     pub fn find_transaction(&self, tx_hash: &str) -> Option<&Transaction> {
-        self.tx_map.get(tx_hash)
+        self.mempool.get(tx_hash).or_else(|| {
+            let location = self.transaction_location(tx_hash)?;
+            self.block_at(location)?.transactions.get(location.position)
+        })
+    }
+}
+
+fn index_transactions(tx_map: &mut TxIndex, block: &Block) {
+    for (position, tx) in block.transactions.iter().enumerate() {
+        let location = TxLocation { block_hash: block.hash.clone(), height: block.index, position };
+        tx_map.insert(tx.tx_hash(), location);
+    }
+}
+
+// só remove se a entrada ainda aponta para este bloco
+fn unindex_transactions(tx_map: &mut TxIndex, block: &Block) {
+    for tx in &block.transactions {
+        let tx_hash = tx.tx_hash();
+        if tx_map.get(&tx_hash).is_some_and(|location| location.block_hash == block.hash) {
+            tx_map.remove(&tx_hash);
+        }
+    }
+}
+
+/// Quantos blocos do começo as duas chains têm em comum (0 se nem o gênese bate).
+pub fn fork_point(a: &[Block], b: &[Block]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x.hash == y.hash).count()
+}
+
+pub fn cumulative_work(blocks: &[Block]) -> U256 {
+    blocks
+        .iter()
+        .fold(U256::ZERO, |acc, block| acc.saturating_add(work_from_bits(block.bits)))
+}
+
+/// Validação completa de `block` como próximo bloco de `chain`, onde `ledger` é o
+/// estado depois de `chain`. É a mesma regra para bloco minerado aqui, recebido
+/// da rede ou vindo de um sync. Só altera `ledger` se o bloco for válido.
+pub fn validate_block(
+    chain: &[Block],
+    ledger: &mut Ledger,
+    block: &Block,
+    initial_bits: u32,
+    now: i64,
+    verify_signatures: bool,
+) -> Result<(), BlockError> {
+    let expected = chain.len() as u64;
+    if block.index != expected {
+        return Err(BlockError::NotNextBlock { expected, got: block.index });
+    }
+    if chain.last().map(|parent| &parent.hash) != Some(&block.previous_hash) {
+        return Err(BlockError::WrongPreviousHash);
     }
+    let required = next_bits(chain, initial_bits);
+    if block.bits != required {
+        return Err(BlockError::UnexpectedBits { expected: required, got: block.bits });
+    }
+    check_timestamp(chain, block, now)?;
+    // hash do header, raiz de Merkle, PoW contra o target e limites de tamanho
+    block.check()?;
+    // assinaturas e duplicadas; abaixo de um checkpoint só as duplicadas
+    if verify_signatures {
+        block.check_transactions()?;
+    } else {
+        block.check_duplicates()?;
+    }
+    // coinbase, nonces e saldos (atômico)
+    ledger.apply_block(block)?;
+    Ok(())
+}
+
+/// Mediana dos timestamps dos últimos MEDIAN_TIME_SPAN blocos (ou de todos, se houver menos).
+pub fn median_time_past(chain: &[Block]) -> i64 {
+    let start = chain.len().saturating_sub(MEDIAN_TIME_SPAN);
+    let mut times: Vec<i64> = chain[start..].iter().map(|block| block.timestamp).collect();
+    if times.is_empty() {
+        return i64::MIN;
+    }
+    times.sort_unstable();
+    times[times.len() / 2]
+}
+
+/// Regras de tempo para `block` em cima de `chain`: estritamente depois do
+/// median-time-past e no máximo MAX_FUTURE_BLOCK_TIME à frente de `now`.
+pub fn check_timestamp(chain: &[Block], block: &Block, now: i64) -> Result<(), BlockError> {
+    let median_time_past = median_time_past(chain);
+    if block.timestamp <= median_time_past {
+        return Err(BlockError::TimestampTooOld { timestamp: block.timestamp, median_time_past });
+    }
+    let max_allowed = now.saturating_add(MAX_FUTURE_BLOCK_TIME);
+    if block.timestamp > max_allowed {
+        return Err(BlockError::TimestampTooFarInFuture { timestamp: block.timestamp, max_allowed });
+    }
+    Ok(())
+}
+
+/// Regra de retarget: fora das fronteiras de RETARGET_INTERVAL o bloco herda os
+/// bits do pai. Na fronteira, o target do pai é multiplicado por tempo real /
+/// tempo esperado da última janela, limitado a MAX_RETARGET_FACTOR e ao POW_LIMIT.
+pub fn next_bits(chain: &[Block], initial_bits: u32) -> u32 {
+    next_bits_at(chain.len() as u64, chain, initial_bits)
+}
+
+/// `next_bits` para o bloco de altura `height` olhando só `recent`, os últimos
+/// blocos antes dele (pelo menos RETARGET_INTERVAL, se a chain tiver tantos).
+pub fn next_bits_at(height: u64, recent: &[Block], initial_bits: u32) -> u32 {
+    let last = match recent.last() {
+        Some(block) => block,
+        None => return initial_bits,
+    };
+    if !height.is_multiple_of(RETARGET_INTERVAL) {
+        return last.bits;
+    }
+
+    let first = &recent[recent.len().saturating_sub(RETARGET_INTERVAL as usize)];
+    let expected = (RETARGET_INTERVAL as i64 - 1) * TARGET_BLOCK_TIME;
+    let actual = (last.timestamp - first.timestamp).max(0);
+
+    let pow_limit = target_from_bits(POW_LIMIT_BITS).expect("POW_LIMIT_BITS válido");
+    let old_target = target_from_bits(last.bits).unwrap_or(pow_limit);
+    let factor = MAX_RETARGET_FACTOR as u64;
+    // multiplica antes de dividir para não perder precisão; se estourar, divide antes
+    let scaled = match old_target.checked_mul_u64(actual as u64) {
+        Some(product) => product.div_u64(expected as u64),
+        None => old_target.div_u64(expected as u64).saturating_mul_u64(actual as u64),
+    };
+    let new_target = scaled
+        .clamp(old_target.div_u64(factor), old_target.saturating_mul_u64(factor))
+        .min(pow_limit);
+
+    bits_from_target(new_target)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blockchain::block::{calculate_hash, compute_merkle_root};
+    use crate::blockchain::pow::{hash_meets_target, U256, INITIAL_BITS};
     use crate::wallet::wallet::generate_wallet;
 
     #[allow(dead_code)]
     fn test_add_valid_transaction_to_mempool_and_find() {
-        let mut blockchain = Blockchain::new();
-
         // Cria 2 carteiras de teste (ajuste se necessário)
         let wallet_from = generate_wallet(); 
         let wallet_to   = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(wallet_from.address.clone(), 100)]);
 
         // Cria uma transação válida (amount>0)
-        let tx = Transaction::new_signed(&wallet_from, wallet_to.address.clone(), 50)
+        let tx = Transaction::new_signed(&wallet_from, wallet_to.address.clone(), 50, 0)
             .expect("Transação deve ser válida com amount=50");
         
         // Pega o hash da transação
         let tx_hash = tx.tx_hash();
 
         // Adiciona ao mempool
-        blockchain.add_transaction_to_mempool(tx);
+        blockchain.add_transaction_to_mempool(tx).expect("tx válida");
 
-        // Verifica se está no tx_map
+        // Verifica se é encontrada (está no mempool)
         let found = blockchain.find_transaction(&tx_hash);
-        assert!(found.is_some(), "A transação deve estar no tx_map");
+        assert!(found.is_some(), "A transação deve ser encontrada");
         let found_tx = found.unwrap();
         assert_eq!(found_tx.tx_hash(), tx_hash, "Os hashes devem bater");
         
-        // Verifica se está na pending_transactions (mempool)
-        assert_eq!(blockchain.pending_transactions.len(), 1, "Deveria haver 1 tx pendente");
-        let mempool_tx = &blockchain.pending_transactions[0];
+        // Verifica se está no mempool
+        assert_eq!(blockchain.mempool.len(), 1, "Deveria haver 1 tx pendente");
+        let mempool_tx = &blockchain.mempool.transactions()[0];
         assert_eq!(mempool_tx.tx_hash(), tx_hash, "Hash no mempool deve bater");
     }
 
@@ -196,7 +1073,7 @@ mod tests {
         let wallet_to   = generate_wallet();
 
         // Cria transação com amount=0 => deve falhar
-        let tx_result = Transaction::new_signed(&wallet_from, wallet_to.address.clone(), 0);
+        let tx_result = Transaction::new_signed(&wallet_from, wallet_to.address.clone(), 0, 0);
         assert!(tx_result.is_err(), "Transação com amount=0 deve retornar Err");
 
         // Se o tx_result é Err, a gente não chega a inserir nada. 
@@ -205,43 +1082,46 @@ mod tests {
             from_address: wallet_from.address.clone(),
             to_address: wallet_to.address.clone(),
             amount: 0,
+            fee: 0,
+            nonce: 0,
             public_key: Some(wallet_from.public_key),
             signature: None, // sem assinar
         };
 
         // Adiciona ao mempool
-        blockchain.add_transaction_to_mempool(invalid_tx);
+        let result = blockchain.add_transaction_to_mempool(invalid_tx);
+        assert!(result.is_err(), "Transação sem assinatura deve ser rejeitada");
 
         // Verifica se mempool continua vazio 
         // (pois transaction.is_valid() deve retornar false)
-        assert_eq!(blockchain.pending_transactions.len(), 0, 
+        assert_eq!(blockchain.mempool.len(), 0, 
             "Nenhuma transação deve ter sido adicionada ao mempool");
 
-        // Verifica se tx_map está vazio também
-        assert_eq!(blockchain.tx_map.len(), 0, 
+        // Verifica que o tx_map continua só com as alocações do gênese
+        assert_eq!(blockchain.tx_map.len(), blockchain.blocks[0].transactions.len(), 
             "Nenhuma transação deve ter sido indexada no tx_map");
     }
 
     #[test]
     fn test_new_signed_tx_and_added_mempool() {
-        let mut blockchain = Blockchain::new();
         let wallet_from = generate_wallet();
         let wallet_to   = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(wallet_from.address.clone(), 100)]);
 
         // Chama a função do Blockchain que cria e adiciona a tx
         blockchain.new_signed_tx_and_added_mempool(&wallet_from, wallet_to.address.clone(), 25);
 
         // Se foi válida, deve estar no mempool
-        assert_eq!(blockchain.pending_transactions.len(), 1);
+        assert_eq!(blockchain.mempool.len(), 1);
         
-        // E também no tx_map
+        // tx_map só indexa transações confirmadas (aqui, só as do gênese)
         assert_eq!(blockchain.tx_map.len(), 1);
 
         // Pega a transação do mempool
-        let mempool_tx = &blockchain.pending_transactions[0];
+        let mempool_tx = &blockchain.mempool.transactions()[0];
         let txid = mempool_tx.tx_hash();
 
-        // Tenta encontrar no tx_map
+        // Tenta encontrar pelo hash
         let found = blockchain.find_transaction(&txid);
         assert!(found.is_some());
         let found_tx = found.unwrap();
@@ -251,4 +1131,1006 @@ mod tests {
         assert_eq!(found_tx.from_address, wallet_from.address);
         assert_eq!(found_tx.to_address, wallet_to.address);
     }
+
+    #[test]
+    fn test_overspending_transaction_is_rejected() {
+        let wallet_from = generate_wallet();
+        let wallet_to   = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(wallet_from.address.clone(), 100)]);
+
+        let tx1 = Transaction::new_signed(&wallet_from, wallet_to.address.clone(), 70, 0).unwrap();
+        blockchain.add_transaction_to_mempool(tx1).expect("saldo suficiente");
+
+        // 70 já está pendente, sobram 30
+        let tx2 = Transaction::new_signed(&wallet_from, wallet_to.address.clone(), 40, 1).unwrap();
+        let result = blockchain.add_transaction_to_mempool(tx2);
+        assert!(matches!(result, Err(TransactionError::InsufficientFunds { .. })));
+        assert_eq!(blockchain.mempool.len(), 1);
+
+        // carteira sem saldo nenhum
+        let tx3 = Transaction::new_signed(&wallet_to, wallet_from.address.clone(), 1, 0).unwrap();
+        assert!(blockchain.add_transaction_to_mempool(tx3).is_err());
+    }
+
+    #[test]
+    fn test_ledger_updated_when_block_is_connected() {
+        let wallet_from = generate_wallet();
+        let wallet_to   = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(wallet_from.address.clone(), 100)]);
+        assert_eq!(blockchain.balance_of(&wallet_from.address), 100);
+
+        blockchain.new_signed_tx_and_added_mempool(&wallet_from, wallet_to.address.clone(), 25);
+        blockchain.add_block("miner");
+
+        assert_eq!(blockchain.blocks.len(), 2);
+        assert_eq!(blockchain.balance_of(&wallet_from.address), 75);
+        assert_eq!(blockchain.balance_of(&wallet_to.address), 25);
+        assert_eq!(blockchain.balance_of("miner"), BLOCK_SUBSIDY);
+        assert!(blockchain.is_valid());
+    }
+
+    #[test]
+    fn test_mined_block_starts_with_coinbase() {
+        let miner = generate_wallet();
+        let wallet_to = generate_wallet();
+        let mut blockchain = Blockchain::new();
+
+        // sem saldo, não há o que gastar
+        assert_eq!(blockchain.balance_of(&miner.address), 0);
+        blockchain.add_block(&miner.address);
+
+        let block = blockchain.blocks.last().unwrap();
+        assert_eq!(block.transactions.len(), 1);
+        assert!(block.transactions[0].is_coinbase());
+        assert_eq!(blockchain.balance_of(&miner.address), BLOCK_SUBSIDY);
+
+        // agora a recompensa pode ser gasta
+        blockchain.new_signed_tx_and_added_mempool(&miner, wallet_to.address.clone(), 20);
+        blockchain.add_block(&miner.address);
+        assert_eq!(blockchain.balance_of(&miner.address), 2 * BLOCK_SUBSIDY - 20);
+        assert_eq!(blockchain.balance_of(&wallet_to.address), 20);
+        assert!(blockchain.is_valid());
+    }
+
+    #[test]
+    fn test_is_valid_rejects_over_claiming_coinbase() {
+        let mut blockchain = Blockchain::new();
+        blockchain.add_block("miner");
+        assert!(blockchain.is_valid());
+
+        let coinbase = Transaction::new_coinbase("miner".to_string(), BLOCK_SUBSIDY * 10, 2);
+        let mut block = Block::new(2, vec![coinbase], blockchain.blocks[1].hash.clone(), blockchain.required_bits(2));
+        block.mine_block();
+        blockchain.blocks.push(block);
+
+        assert!(!blockchain.is_valid());
+    }
+
+    #[test]
+    fn test_is_valid_rejects_overspending_block() {
+        let wallet_from = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(wallet_from.address.clone(), 10)]);
+
+        // bloco montado à mão, sem passar pelo mempool
+        let tx = Transaction::new_signed(&wallet_from, "Bob".to_string(), 500, 0).unwrap();
+        let coinbase = Transaction::new_coinbase("miner".to_string(), BLOCK_SUBSIDY, 1);
+        let mut block = Block::new(1, vec![coinbase, tx], blockchain.blocks[0].hash.clone(), blockchain.required_bits(1));
+        block.mine_block();
+        blockchain.blocks.push(block);
+
+        assert!(!blockchain.is_valid());
+    }
+
+    // monta uma chain só com timestamps, sem minerar (next_bits só olha tempos e bits)
+    fn chain_with_spacing(len: usize, spacing: i64) -> Vec<Block> {
+        (0..len)
+            .map(|i| {
+                let mut block = Block::new(i as u64, vec![], "0".to_string(), INITIAL_BITS);
+                block.timestamp = 1_000_000 + i as i64 * spacing;
+                block
+            })
+            .collect()
+    }
+
+    fn target(bits: u32) -> U256 {
+        target_from_bits(bits).unwrap()
+    }
+
+    #[test]
+    fn test_bits_constant_before_first_retarget() {
+        let chain = chain_with_spacing(RETARGET_INTERVAL as usize - 1, 0);
+        assert_eq!(next_bits(&chain, INITIAL_BITS), INITIAL_BITS);
+        assert_eq!(next_bits(&[], INITIAL_BITS), INITIAL_BITS);
+    }
+
+    #[test]
+    fn test_retarget_up_and_down() {
+        let interval = RETARGET_INTERVAL as usize;
+        let initial = target(INITIAL_BITS);
+
+        // blocos rápidos demais => target menor, limitado a 1/4
+        let fast = chain_with_spacing(interval, 0);
+        assert_eq!(target(next_bits(&fast, INITIAL_BITS)), initial.div_u64(4));
+
+        // 3x mais lento => target 3x maior
+        let slow = chain_with_spacing(interval, TARGET_BLOCK_TIME * 3);
+        assert_eq!(target(next_bits(&slow, INITIAL_BITS)), initial.saturating_mul_u64(3));
+
+        // no tempo certo => mantém
+        let on_time = chain_with_spacing(interval, TARGET_BLOCK_TIME);
+        assert_eq!(next_bits(&on_time, INITIAL_BITS), INITIAL_BITS);
+
+        // nunca mais fácil que o POW_LIMIT
+        let mut at_limit = chain_with_spacing(interval, TARGET_BLOCK_TIME * 100);
+        for block in at_limit.iter_mut() {
+            block.bits = POW_LIMIT_BITS;
+        }
+        assert_eq!(next_bits(&at_limit, INITIAL_BITS), POW_LIMIT_BITS);
+    }
+
+    #[test]
+    fn test_bits_inherited_between_boundaries() {
+        let interval = RETARGET_INTERVAL as usize;
+        let mut chain = chain_with_spacing(interval + 5, 0);
+        for block in chain.iter_mut().skip(interval) {
+            block.bits = 0x1e40_0000;
+        }
+
+        assert_eq!(next_bits(&chain[..interval + 1], INITIAL_BITS), 0x1e40_0000);
+        assert_eq!(next_bits(&chain, INITIAL_BITS), 0x1e40_0000);
+    }
+
+    #[test]
+    fn test_is_valid_checks_proof_of_work() {
+        let mut blockchain = Blockchain::new();
+        blockchain.add_block("miner");
+        assert!(blockchain.is_valid());
+
+        // bloco com hash correto mas acima do target exigido
+        let coinbase = Transaction::new_coinbase("miner".to_string(), BLOCK_SUBSIDY, 2);
+        let mut block = Block::new(2, vec![coinbase], blockchain.blocks[1].hash.clone(), blockchain.required_bits(2));
+        while hash_meets_target(&block.hash, block.bits) {
+            block.nonce += 1;
+            block.hash = calculate_hash(
+                block.index, block.timestamp, &block.merkle_root, &block.previous_hash, block.bits, block.nonce,
+            );
+        }
+        assert!(blockchain.add_block_from_network(block.clone()).is_err());
+        assert_eq!(blockchain.blocks.len(), 2, "add_block_from_network deve rejeitar bloco sem PoW");
+
+        blockchain.blocks.push(block);
+        assert!(!blockchain.is_valid());
+    }
+
+    #[test]
+    fn test_block_with_easier_bits_is_rejected() {
+        let mut blockchain = Blockchain::new();
+        blockchain.add_block("miner");
+
+        // minerado contra um target mais fácil que o exigido: o PoW confere, mas os bits não
+        let coinbase = Transaction::new_coinbase("miner".to_string(), BLOCK_SUBSIDY, 2);
+        let mut block = Block::new(2, vec![coinbase], blockchain.blocks[1].hash.clone(), POW_LIMIT_BITS);
+        block.mine_block();
+        assert!(block.is_valid());
+
+        assert!(blockchain.add_block_from_network(block.clone()).is_err());
+        assert_eq!(blockchain.blocks.len(), 2);
+
+        blockchain.blocks.push(block);
+        assert!(!blockchain.is_valid());
+    }
+
+    #[test]
+    fn test_chain_work_grows_with_each_block() {
+        let mut blockchain = Blockchain::new();
+        let genesis_work = blockchain.chain_work();
+        assert_eq!(genesis_work, work_from_bits(INITIAL_BITS));
+
+        blockchain.add_block("miner");
+        assert_eq!(blockchain.chain_work(), genesis_work.saturating_add(work_from_bits(INITIAL_BITS)));
+
+        // o valor guardado no índice é o mesmo da soma bloco a bloco
+        let tip = blockchain.blocks.last().unwrap().hash.clone();
+        assert_eq!(blockchain.index.get(&tip).unwrap().chain_work, cumulative_work(&blockchain.blocks));
+
+        // chain trocada por fora, sem passar pelo índice: soma os blocos
+        let mut detached = blockchain.clone();
+        detached.blocks.pop();
+        detached.blocks[0].nonce += 1;
+        detached.blocks[0].hash = detached.blocks[0].header_hash();
+        assert_eq!(detached.chain_work(), genesis_work);
+    }
+
+    #[test]
+    fn test_longer_but_lighter_chain_is_not_adopted() {
+        // chain curta com target 4x mais difícil desde o gênese
+        const HARD_BITS: u32 = 0x1f04_0000;
+        let mut heavy = Blockchain::new();
+        heavy.initial_bits = HARD_BITS;
+        heavy.blocks[0].bits = HARD_BITS;
+        heavy.blocks[0].mine_block();
+        heavy.add_block("honest");
+        assert_eq!(heavy.blocks.len(), 2);
+
+        // chain mais longa, mas com blocos fáceis
+        let mut light = Blockchain::new();
+        for _ in 0..3 {
+            light.add_block("attacker");
+        }
+        assert!(light.blocks.len() > heavy.blocks.len());
+        assert!(heavy.has_more_work_than(&light));
+
+        assert!(!heavy.replace_chain_if_heavier(&light.blocks));
+        assert_eq!(heavy.blocks.len(), 2);
+
+        // o contrário tem mais trabalho, mas o gênese é outro: não há ponto de fork
+        assert!(heavy.has_more_work_than(&light));
+        assert!(!light.replace_chain_if_heavier(&heavy.blocks));
+        assert_eq!(light.blocks.len(), 4);
+    }
+
+    #[test]
+    fn test_transaction_proof_verifies_against_header() {
+        use crate::blockchain::merkle::verify_transaction_proof;
+
+        let wallet_from = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(wallet_from.address.clone(), 100)]);
+        for amount in [10, 20, 30] {
+            blockchain.new_signed_tx_and_added_mempool(&wallet_from, "Bob".to_string(), amount);
+        }
+        blockchain.add_block("miner");
+
+        let block = blockchain.blocks[1].clone();
+        let tx_hash = block.transactions[2].tx_hash();
+        let (proof, height) = blockchain.transaction_proof(&tx_hash).expect("tx confirmada");
+        assert_eq!(height, 1);
+        assert_eq!(proof.header, block.header());
+        assert!(verify_transaction_proof(&proof));
+
+        // adulterando o header ou a transação a prova deixa de valer
+        let mut wrong_tx = proof.clone();
+        wrong_tx.tx_hash = block.transactions[1].tx_hash();
+        assert!(!verify_transaction_proof(&wrong_tx));
+
+        let mut wrong_root = proof.clone();
+        wrong_root.header.merkle_root = "ab".repeat(32);
+        assert!(!verify_transaction_proof(&wrong_root));
+
+        let mut wrong_position = proof.clone();
+        wrong_position.position = 3;
+        assert!(!verify_transaction_proof(&wrong_position));
+
+        assert!(blockchain.transaction_proof("nao-existe").is_none());
+    }
+
+    #[test]
+    fn test_template_then_connect_purges_mempool() {
+        let wallet_from = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(wallet_from.address.clone(), 100)]);
+        blockchain.new_signed_tx_and_added_mempool(&wallet_from, "Bob".to_string(), 30);
+
+        let mut template = blockchain.create_block_template("miner");
+        assert_eq!(template.transactions.len(), 2);
+        // montar o template não consome o mempool
+        assert_eq!(blockchain.mempool.len(), 1);
+
+        template.mine_block();
+        blockchain.connect_block(template.clone()).expect("bloco válido");
+        assert!(blockchain.mempool.is_empty());
+        assert_eq!(blockchain.balance_of("Bob"), 30);
+
+        // o mesmo bloco de novo já não é o próximo
+        assert_eq!(
+            blockchain.connect_block(template),
+            Err(BlockError::NotNextBlock { expected: 2, got: 1 })
+        );
+    }
+
+    #[test]
+    fn test_connect_block_reports_reason() {
+        let mut blockchain = Blockchain::new();
+        let mut template = blockchain.create_block_template("miner");
+        template.mine_block();
+
+        let mut wrong_parent = template.clone();
+        wrong_parent.previous_hash = "outro".to_string();
+        assert_eq!(blockchain.connect_block(wrong_parent), Err(BlockError::WrongPreviousHash));
+
+        let mut tampered = template.clone();
+        tampered.transactions[0].amount = 1;
+        assert_eq!(blockchain.connect_block(tampered), Err(BlockError::InvalidMerkleRoot));
+
+        let mut wrong_hash = template.clone();
+        wrong_hash.hash = "00".repeat(32);
+        assert_eq!(blockchain.connect_block(wrong_hash), Err(BlockError::InvalidHash));
+
+        assert!(blockchain.connect_block(template).is_ok());
+    }
+
+    #[test]
+    fn test_median_time_past_uses_last_blocks() {
+        let chain = chain_with_spacing(20, 10);
+        // últimos 11 blocos: alturas 9..=19, mediana é a altura 14
+        assert_eq!(median_time_past(&chain), chain[14].timestamp);
+        assert_eq!(median_time_past(&chain[..3]), chain[1].timestamp);
+    }
+
+    #[test]
+    fn test_timestamp_rules() {
+        let chain = chain_with_spacing(12, 10);
+        let mtp = median_time_past(&chain);
+        let now = chain.last().unwrap().timestamp;
+        let mut block = Block::new(12, vec![], chain[11].hash.clone(), INITIAL_BITS);
+
+        block.timestamp = mtp;
+        assert_eq!(
+            check_timestamp(&chain, &block, now),
+            Err(BlockError::TimestampTooOld { timestamp: mtp, median_time_past: mtp })
+        );
+
+        // pode ser anterior ao pai, desde que passe da mediana
+        block.timestamp = mtp + 1;
+        assert!(block.timestamp < chain[11].timestamp);
+        assert!(check_timestamp(&chain, &block, now).is_ok());
+
+        block.timestamp = now + MAX_FUTURE_BLOCK_TIME + 1;
+        assert_eq!(
+            check_timestamp(&chain, &block, now),
+            Err(BlockError::TimestampTooFarInFuture {
+                timestamp: block.timestamp,
+                max_allowed: now + MAX_FUTURE_BLOCK_TIME
+            })
+        );
+    }
+
+    #[test]
+    fn test_connect_block_rejects_future_timestamp() {
+        let mut blockchain = Blockchain::new();
+        let mut template = blockchain.create_block_template("miner");
+        template.timestamp += MAX_FUTURE_BLOCK_TIME + 60;
+        template.mine_block();
+        assert!(matches!(
+            blockchain.connect_block(template),
+            Err(BlockError::TimestampTooFarInFuture { .. })
+        ));
+    }
+
+    #[test]
+    fn test_template_timestamp_is_after_median_time_past() {
+        let mut blockchain = Blockchain::new();
+        for _ in 0..3 {
+            blockchain.add_block("miner");
+        }
+        // blocos minerados no mesmo segundo continuam válidos
+        assert_eq!(blockchain.blocks.len(), 4);
+        let template = blockchain.create_block_template("miner");
+        assert!(template.timestamp > median_time_past(&blockchain.blocks));
+        assert!(blockchain.is_valid());
+    }
+
+    #[test]
+    fn test_replayed_transaction_is_rejected() {
+        let wallet_from = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(wallet_from.address.clone(), 100)]);
+
+        let tx = Transaction::new_signed(&wallet_from, "Bob".to_string(), 10, 0).unwrap();
+        blockchain.add_transaction_to_mempool(tx.clone()).unwrap();
+        // duplicada no mempool
+        assert!(matches!(
+            blockchain.add_transaction_to_mempool(tx.clone()),
+            Err(TransactionError::InvalidNonce { expected: 1, got: 0, .. })
+        ));
+
+        blockchain.add_block("miner");
+        assert_eq!(blockchain.balance_of("Bob"), 10);
+        assert_eq!(blockchain.next_nonce(&wallet_from.address), 1);
+
+        // depois de minerada, reenviar a mesma transação não paga Bob de novo
+        assert!(blockchain.add_transaction_to_mempool(tx.clone()).is_err());
+        let coinbase = Transaction::new_coinbase("miner".to_string(), BLOCK_SUBSIDY, 2);
+        let mut replay = Block::new(2, vec![coinbase, tx], blockchain.blocks[1].hash.clone(), blockchain.required_bits(2));
+        replay.timestamp = median_time_past(&blockchain.blocks) + 1;
+        replay.mine_block();
+        assert!(matches!(
+            blockchain.connect_block(replay),
+            Err(BlockError::Transaction(TransactionError::InvalidNonce { .. }))
+        ));
+
+        // nonce pulado
+        let gap = Transaction::new_signed(&wallet_from, "Bob".to_string(), 10, 5).unwrap();
+        assert!(matches!(
+            blockchain.add_transaction_to_mempool(gap),
+            Err(TransactionError::InvalidNonce { expected: 1, got: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn test_template_prefers_fee_rate_and_keeps_nonce_order() {
+        let alice = generate_wallet();
+        let bob = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[
+            (alice.address.clone(), 100),
+            (bob.address.clone(), 100),
+        ]);
+
+        // alice: taxa baixa e depois alta; bob: taxa média
+        let a0 = Transaction::new_signed_with_fee(&alice, "Carol".to_string(), 10, 1, 0).unwrap();
+        let a1 = Transaction::new_signed_with_fee(&alice, "Carol".to_string(), 10, 9, 1).unwrap();
+        let b0 = Transaction::new_signed_with_fee(&bob, "Carol".to_string(), 10, 5, 0).unwrap();
+        for tx in [a0.clone(), a1.clone(), b0.clone()] {
+            blockchain.add_transaction_to_mempool(tx).unwrap();
+        }
+
+        // cabem só duas: b0 tem o melhor fee rate entre as primeiras de cada remetente
+        let (selected, reward) = blockchain.select_transactions(MAX_BLOCK_SIZE, 2);
+        assert_eq!(selected, vec![b0.clone(), a0.clone()]);
+        assert_eq!(reward, BLOCK_SUBSIDY + 6);
+
+        // sem limite, a1 só pode vir depois de a0
+        let template = blockchain.create_block_template("miner");
+        assert_eq!(template.transactions[1..], [b0, a0, a1]);
+        assert_eq!(template.transactions[0].amount, BLOCK_SUBSIDY + 15);
+
+        blockchain.add_block("miner");
+        assert_eq!(blockchain.balance_of("miner"), BLOCK_SUBSIDY + 15);
+        assert_eq!(blockchain.balance_of(&alice.address), 100 - 20 - 10);
+        assert!(blockchain.is_valid());
+    }
+
+    #[test]
+    fn test_template_respects_size_limit() {
+        let alice = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(alice.address.clone(), 100)]);
+        let tx = Transaction::new_signed_with_fee(&alice, "Carol".to_string(), 10, 1, 0).unwrap();
+        blockchain.add_transaction_to_mempool(tx.clone()).unwrap();
+
+        let (selected, reward) = blockchain.select_transactions(tx.size() - 1, 10);
+        assert!(selected.is_empty());
+        assert_eq!(reward, BLOCK_SUBSIDY);
+
+        // bloco acima do limite é inválido por consenso
+        let mut block = blockchain.create_block_template("miner");
+        block.transactions = vec![block.transactions[0].clone(); MAX_BLOCK_TRANSACTIONS + 1];
+        assert!(matches!(block.check_limits(), Err(BlockError::TooManyTransactions { .. })));
+    }
+
+    #[test]
+    fn test_forged_signature_is_rejected_everywhere() {
+        let alice = generate_wallet();
+        let mallory = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(alice.address.clone(), 100)]);
+
+        // assinatura de outra chave sobre a transação de alice
+        let mut forged = Transaction::new_signed(&alice, mallory.address.clone(), 90, 0).unwrap();
+        forged.signature = Transaction::new_signed(&mallory, mallory.address.clone(), 90, 0).unwrap().signature;
+        assert!(!forged.is_valid());
+
+        let mut block = blockchain.create_block_template("miner");
+        block.transactions.push(forged);
+        block.mine_block();
+        assert!(matches!(
+            blockchain.connect_block(block.clone()),
+            Err(BlockError::Transaction(TransactionError::InvalidSignature(_)))
+        ));
+
+        blockchain.blocks.push(block);
+        assert!(matches!(
+            blockchain.validate(),
+            Err(BlockError::Transaction(TransactionError::InvalidSignature(_)))
+        ));
+        assert!(!blockchain.is_valid());
+    }
+
+    #[test]
+    fn test_duplicate_transaction_in_block_is_rejected() {
+        let alice = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(alice.address.clone(), 100)]);
+        let tx = Transaction::new_signed(&alice, "Bob".to_string(), 10, 0).unwrap();
+
+        let mut block = blockchain.create_block_template("miner");
+        block.transactions.push(tx.clone());
+        block.transactions.push(tx.clone());
+        block.mine_block();
+        assert_eq!(blockchain.connect_block(block), Err(BlockError::DuplicateTransaction(tx.tx_hash())));
+        assert_eq!(blockchain.blocks.len(), 1);
+    }
+
+    #[test]
+    fn test_synced_chain_goes_through_the_same_rules() {
+        let mut local = Blockchain::new();
+        let mut remote = local.clone();
+        remote.add_block("miner");
+        remote.add_block("miner");
+
+        // a mesma chain, mas com a coinbase do último bloco adulterada e re-minerada
+        let mut tampered = remote.clone();
+        let last = tampered.blocks.last_mut().unwrap();
+        last.transactions[0].amount = BLOCK_SUBSIDY * 2;
+        last.mine_block();
+        assert!(matches!(tampered.validate(), Err(BlockError::Transaction(TransactionError::InvalidCoinbase(_)))));
+        assert!(!local.replace_chain_if_heavier(&tampered.blocks));
+
+        assert!(local.replace_chain_if_heavier(&remote.blocks));
+        assert_eq!(local.balance_of("miner"), BLOCK_SUBSIDY * 2);
+    }
+
+    #[test]
+    fn test_spending_someone_elses_address_is_rejected() {
+        let victim = generate_wallet();
+        let attacker = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(victim.address.clone(), 100)]);
+
+        let mut tx = Transaction::new_signed(&attacker, attacker.address.clone(), 100, 0).unwrap();
+        tx.from_address = victim.address.clone();
+        tx.signature = Some(crate::wallet::transaction::sign_data(&attacker, &tx.signing_payload()));
+
+        assert!(matches!(
+            blockchain.add_transaction_to_mempool(tx.clone()),
+            Err(TransactionError::AddressMismatch { .. })
+        ));
+
+        let mut block = blockchain.create_block_template("miner");
+        block.transactions.push(tx);
+        block.mine_block();
+        assert!(matches!(
+            blockchain.connect_block(block),
+            Err(BlockError::Transaction(TransactionError::AddressMismatch { .. }))
+        ));
+        assert_eq!(blockchain.balance_of(&victim.address), 100);
+    }
+
+    #[test]
+    fn test_reorg_returns_orphaned_transactions_to_mempool() {
+        let alice = generate_wallet();
+        let genesis = Blockchain::with_genesis_allocations(&[(alice.address.clone(), 100)]);
+
+        // local: tx de alice confirmada num bloco que vai perder
+        let mut local = genesis.clone();
+        let tx = Transaction::new_signed(&alice, "Bob".to_string(), 10, 0).unwrap();
+        local.add_transaction_to_mempool(tx.clone()).unwrap();
+        local.add_block("local-miner");
+        assert_eq!(local.balance_of("Bob"), 10);
+        assert!(local.find_transaction(&tx.tx_hash()).is_some());
+
+        // remoto: dois blocos vazios a partir do gênese, mais trabalho
+        let mut remote = genesis.clone();
+        remote.add_block("remote-miner");
+        remote.add_block("remote-miner");
+
+        assert!(local.replace_chain_if_heavier(&remote.blocks));
+        assert_eq!(local.blocks, remote.blocks);
+        assert_eq!(local.balance_of("Bob"), 0);
+        assert_eq!(local.balance_of("local-miner"), 0);
+        // desfazer o bloco local dá o mesmo ledger que reprocessar a chain nova
+        assert_eq!(local.ledger, Ledger::from_blocks(&remote.blocks).unwrap());
+        // a transação volta a ficar pendente e entra no próximo bloco
+        assert_eq!(local.mempool.transactions(), vec![tx.clone()]);
+        local.add_block("local-miner");
+        assert_eq!(local.balance_of("Bob"), 10);
+        assert!(local.is_valid());
+    }
+
+    #[test]
+    fn test_reorg_purges_transactions_confirmed_in_new_chain() {
+        let alice = generate_wallet();
+        let genesis = Blockchain::with_genesis_allocations(&[(alice.address.clone(), 100)]);
+        let tx0 = Transaction::new_signed(&alice, "Bob".to_string(), 10, 0).unwrap();
+        let tx1 = Transaction::new_signed(&alice, "Bob".to_string(), 10, 1).unwrap();
+
+        let mut local = genesis.clone();
+        local.add_transaction_to_mempool(tx0.clone()).unwrap();
+        local.add_transaction_to_mempool(tx1.clone()).unwrap();
+
+        // o remoto confirmou só tx0
+        let mut remote = genesis.clone();
+        remote.add_transaction_to_mempool(tx0.clone()).unwrap();
+        remote.add_block("remote-miner");
+
+        assert!(local.replace_chain_if_heavier(&remote.blocks));
+        assert_eq!(local.mempool.transactions(), vec![tx1]);
+        assert_eq!(local.next_nonce(&alice.address), 2);
+    }
+
+    #[test]
+    fn test_reorg_rejects_different_genesis() {
+        let mut local = Blockchain::new();
+        let mut other = Blockchain::with_genesis_allocations(&[("someone".to_string(), 1)]);
+        other.add_block("miner");
+        assert_eq!(local.reorganize(&other.blocks), Err(BlockError::DifferentGenesis));
+        assert!(!local.replace_chain_if_heavier(&other.blocks));
+    }
+
+    #[test]
+    fn test_orphans_connect_once_ancestry_arrives() {
+        let genesis = Blockchain::new();
+        let mut source = genesis.clone();
+        for _ in 0..3 {
+            source.add_block("miner");
+        }
+
+        let mut local = genesis.clone();
+        assert_eq!(
+            local.add_block_from_network(source.blocks[3].clone()),
+            Ok(BlockStatus::Orphaned { missing_parent: source.blocks[2].hash.clone() })
+        );
+        // o que falta pedir é sempre o pai do órfão mais antigo
+        assert_eq!(
+            local.add_block_from_network(source.blocks[2].clone()),
+            Ok(BlockStatus::Orphaned { missing_parent: source.blocks[1].hash.clone() })
+        );
+        assert_eq!(local.add_block_from_network(source.blocks[3].clone()), Ok(BlockStatus::AlreadyKnown));
+        assert_eq!(local.orphans.len(), 2);
+
+        assert_eq!(local.add_block_from_network(source.blocks[1].clone()), Ok(BlockStatus::Connected));
+        assert_eq!(local.blocks, source.blocks);
+        assert!(local.orphans.is_empty());
+        assert_eq!(local.balance_of("miner"), 3 * BLOCK_SUBSIDY);
+    }
+
+    #[test]
+    fn test_orphan_branch_with_more_work_reorganizes() {
+        let genesis = Blockchain::new();
+        let mut local = genesis.clone();
+        local.add_block("local-miner");
+        let mut remote = genesis.clone();
+        remote.add_block("remote-miner");
+        remote.add_block("remote-miner");
+
+        // o primeiro bloco remoto sozinho empata em trabalho: fica de lado
+        assert_eq!(local.add_block_from_network(remote.blocks[1].clone()), Ok(BlockStatus::SideBranch));
+        assert_eq!(local.blocks.len(), 2);
+        assert_ne!(local.blocks[1].hash, remote.blocks[1].hash);
+
+        assert_eq!(local.add_block_from_network(remote.blocks[2].clone()), Ok(BlockStatus::Reorganized));
+        assert_eq!(local.blocks, remote.blocks);
+        assert_eq!(local.balance_of("local-miner"), 0);
+        assert!(local.orphans.is_empty());
+    }
+
+    // mesmo hash e mesma raiz, mas com a última transação repetida
+    fn mutated_twin(block: &Block) -> Block {
+        let mut twin = block.clone();
+        twin.transactions.push(block.transactions.last().unwrap().clone());
+        assert_eq!(twin.merkle_root, compute_merkle_root(&twin.transactions));
+        twin
+    }
+
+    // bloco com coinbase + duas transações de `wallet`: número ímpar de folhas
+    fn add_block_with_two_transfers(blockchain: &mut Blockchain, wallet: &Wallet, miner: &str) {
+        for _ in 0..2 {
+            blockchain.new_signed_tx_and_added_mempool(wallet, "Bob".to_string(), 1);
+        }
+        blockchain.add_block(miner);
+        assert_eq!(blockchain.blocks.last().unwrap().transactions.len(), 3);
+    }
+
+    #[test]
+    fn test_mutated_twin_does_not_shadow_side_block() {
+        let alice = generate_wallet();
+        let genesis = Blockchain::with_genesis_allocations(&[(alice.address.clone(), 100)]);
+        let mut remote = genesis.clone();
+        add_block_with_two_transfers(&mut remote, &alice, "remote-miner");
+        remote.add_block("remote-miner");
+        let mut local = genesis.clone();
+        local.add_block("local-miner");
+
+        // o gêmeo chega primeiro e não pode ocupar o hash do bloco honesto
+        let real = remote.blocks[1].clone();
+        let twin = mutated_twin(&real);
+        assert_eq!(
+            local.add_block_from_network(twin),
+            Err(BlockError::DuplicateTransaction(real.transactions[2].tx_hash()))
+        );
+        assert!(local.get_block(&real.hash).is_none());
+
+        assert_eq!(local.add_block_from_network(real), Ok(BlockStatus::SideBranch));
+        assert_eq!(local.add_block_from_network(remote.blocks[2].clone()), Ok(BlockStatus::Reorganized));
+        assert_eq!(local.blocks, remote.blocks);
+    }
+
+    #[test]
+    fn test_mutated_twin_does_not_shadow_orphan() {
+        let alice = generate_wallet();
+        let genesis = Blockchain::with_genesis_allocations(&[(alice.address.clone(), 100)]);
+        let mut remote = genesis.clone();
+        remote.add_block("remote-miner");
+        add_block_with_two_transfers(&mut remote, &alice, "remote-miner");
+        let mut local = genesis.clone();
+
+        let real = remote.blocks[2].clone();
+        assert!(matches!(
+            local.add_block_from_network(mutated_twin(&real)),
+            Err(BlockError::DuplicateTransaction(_))
+        ));
+        assert!(!local.orphans.contains(&real.hash));
+
+        assert_eq!(
+            local.add_block_from_network(real),
+            Ok(BlockStatus::Orphaned { missing_parent: remote.blocks[1].hash.clone() })
+        );
+        assert_eq!(local.add_block_from_network(remote.blocks[1].clone()), Ok(BlockStatus::Connected));
+        assert_eq!(local.blocks, remote.blocks);
+    }
+
+    #[test]
+    fn test_invalid_side_branch_is_dropped_from_index() {
+        let genesis = Blockchain::new();
+        let mut local = genesis.clone();
+        local.add_block("local-miner");
+        let mut remote = genesis.clone();
+        remote.add_block("remote-miner");
+
+        // mais trabalho, mas a coinbase pede demais
+        let coinbase = Transaction::new_coinbase("remote-miner".to_string(), 1000, 2);
+        let mut greedy = Block::new(2, vec![coinbase], remote.blocks[1].hash.clone(), remote.required_bits(2));
+        greedy.timestamp = remote.blocks[1].timestamp + 1;
+        greedy.mine_block();
+
+        assert_eq!(local.add_block_from_network(remote.blocks[1].clone()), Ok(BlockStatus::SideBranch));
+        assert!(matches!(
+            local.add_block_from_network(greedy.clone()),
+            Err(BlockError::Transaction(TransactionError::InvalidCoinbase(_)))
+        ));
+        // a chain ativa continua a local; o ramo remoto volta a terminar no bloco 1
+        assert_eq!(local.blocks.len(), 2);
+        assert_ne!(local.blocks[1].hash, remote.blocks[1].hash);
+        assert!(local.get_block(&greedy.hash).is_none());
+        assert_eq!(local.tips().len(), 2);
+        assert!(local.is_valid());
+    }
+
+    #[test]
+    fn test_side_block_must_follow_its_branch_rules() {
+        let genesis = Blockchain::new();
+        let mut local = genesis.clone();
+        local.add_block("local-miner");
+        let known = local.index.len();
+
+        // PoW fácil contra os próprios bits: passaria no check() sozinho
+        let coinbase = Transaction::new_coinbase("spammer".to_string(), BLOCK_SUBSIDY, 1);
+        let mut cheap = Block::new(1, vec![coinbase.clone()], genesis.blocks[0].hash.clone(), POW_LIMIT_BITS);
+        cheap.mine_block();
+        assert_eq!(
+            local.add_block_from_network(cheap),
+            Err(BlockError::UnexpectedBits { expected: INITIAL_BITS, got: POW_LIMIT_BITS })
+        );
+
+        // bits certos, mas timestamp que não passa do median-time-past do ramo
+        let mut stale = Block::new(1, vec![coinbase], genesis.blocks[0].hash.clone(), INITIAL_BITS);
+        stale.timestamp = genesis.blocks[0].timestamp;
+        stale.mine_block();
+        assert!(matches!(local.add_block_from_network(stale), Err(BlockError::TimestampTooOld { .. })));
+        assert_eq!(local.index.len(), known);
+        assert_eq!(local.tips().len(), 1);
+    }
+
+    // minera no ritmo de TARGET_BLOCK_TIME, para a dificuldade não subir
+    fn mine_on_schedule(blockchain: &mut Blockchain, count: usize) {
+        for _ in 0..count {
+            let mut block = blockchain.create_block_template("miner");
+            block.timestamp = blockchain.blocks.last().unwrap().timestamp + TARGET_BLOCK_TIME;
+            block.mine_block();
+            blockchain.connect_block(block).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_side_branch_far_behind_is_not_stored() {
+        // dificuldade mínima: o teste precisa de mais de MAX_SIDE_BRANCH_DEPTH blocos
+        let spec = ChainSpec { initial_bits: POW_LIMIT_BITS, ..ChainSpec::default() };
+        let genesis = Blockchain::from_spec(&spec).unwrap();
+        let mut local = genesis.clone();
+        mine_on_schedule(&mut local, MAX_SIDE_BRANCH_DEPTH + 2);
+
+        let mut deep = genesis.clone();
+        mine_on_schedule(&mut deep, 1);
+        deep.blocks[1].transactions[0].to_address = "rival".to_string();
+        deep.blocks[1].mine_block();
+        assert_eq!(
+            local.add_block_from_network(deep.blocks[1].clone()),
+            Err(BlockError::BranchTooFarBehind { height: 1 })
+        );
+
+        // um fork recente continua sendo guardado
+        let mut recent = local.clone();
+        recent.blocks.pop();
+        let mut rival = recent.create_block_template("rival");
+        rival.timestamp = recent.blocks.last().unwrap().timestamp + TARGET_BLOCK_TIME;
+        rival.mine_block();
+        assert_eq!(local.add_block_from_network(rival), Ok(BlockStatus::SideBranch));
+        assert_eq!(local.tips().len(), 2);
+    }
+
+    #[test]
+    fn test_independent_nodes_share_genesis() {
+        let a = Blockchain::new();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        let b = Blockchain::new();
+        assert_eq!(a.blocks[0], b.blocks[0]);
+
+        let pinned = ChainSpec { genesis_hash: Some("ff".repeat(32)), ..ChainSpec::default() };
+        assert!(matches!(Blockchain::from_spec(&pinned), Err(ChainSpecError::GenesisMismatch { .. })));
+    }
+
+    fn spec_with_checkpoint(height: u64, hash: &str) -> ChainSpec {
+        ChainSpec {
+            checkpoints: vec![Checkpoint { height, hash: hash.to_string() }],
+            ..ChainSpec::default()
+        }
+    }
+
+    #[test]
+    fn test_checkpoints_reject_other_history() {
+        let mut source = Blockchain::new();
+        source.add_block("miner");
+        source.add_block("miner");
+        let spec = spec_with_checkpoint(2, &source.blocks[2].hash);
+
+        let mut rival = Blockchain::new();
+        for _ in 0..4 {
+            rival.add_block("rival");
+        }
+
+        let mut local = Blockchain::from_spec(&spec).unwrap();
+        assert_eq!(local.add_block_from_network(source.blocks[1].clone()), Ok(BlockStatus::Connected));
+        assert!(matches!(
+            local.add_block_from_network(rival.blocks[2].clone()),
+            Err(BlockError::CheckpointMismatch { height: 2, .. })
+        ));
+        assert_eq!(local.add_block_from_network(source.blocks[2].clone()), Ok(BlockStatus::Connected));
+
+        // mais trabalho, mas desfaria o checkpoint
+        assert!(rival.has_more_work_than(&local));
+        assert_eq!(
+            local.reorganize(&rival.blocks),
+            Err(BlockError::ReorgBelowCheckpoint { fork_height: 1, checkpoint_height: 2 })
+        );
+        assert!(!local.replace_chain_if_heavier(&rival.blocks));
+        assert_eq!(
+            local.add_block_from_network(rival.blocks[1].clone()),
+            Err(BlockError::ReorgBelowCheckpoint { fork_height: 1, checkpoint_height: 2 })
+        );
+        assert_eq!(local.blocks, source.blocks);
+        assert_eq!(local.tips().len(), 1);
+    }
+
+    #[test]
+    fn test_signatures_below_checkpoint_are_not_rechecked() {
+        let alice = generate_wallet();
+        let mallory = generate_wallet();
+        let mut blockchain = Blockchain::with_genesis_allocations(&[(alice.address.clone(), 100)]);
+
+        // bloco com assinatura forjada; só um checkpoint explica ele estar na chain
+        let mut forged = Transaction::new_signed(&alice, mallory.address.clone(), 90, 0).unwrap();
+        forged.signature = Transaction::new_signed(&mallory, mallory.address.clone(), 90, 0).unwrap().signature;
+        let mut block = blockchain.create_block_template("miner");
+        block.transactions.push(forged);
+        block.mine_block();
+        blockchain.blocks.push(block.clone());
+        assert!(matches!(
+            blockchain.validate(),
+            Err(BlockError::Transaction(TransactionError::InvalidSignature(_)))
+        ));
+
+        blockchain.checkpoints = vec![Checkpoint { height: 1, hash: block.hash.clone() }];
+        assert!(blockchain.validate().is_ok());
+    }
+
+    #[test]
+    fn test_chain_and_mempool_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("blockchainpow-chain-{}", uuid::Uuid::new_v4()));
+        let alice = generate_wallet();
+        let allocations = [(alice.address.clone(), 100)];
+
+        let mut rival = Blockchain::with_genesis_allocations(&allocations);
+        rival.add_block("rival");
+        let (blocks, pending) = {
+            let mut blockchain = Blockchain::with_genesis_allocations(&allocations);
+            blockchain.attach_store(&dir).unwrap();
+            blockchain.new_signed_tx_and_added_mempool(&alice, "Bob".to_string(), 10);
+            blockchain.add_block("miner");
+            blockchain.add_block("miner");
+            // ramo lateral também vai para o disco
+            assert_eq!(blockchain.add_block_from_network(rival.blocks[1].clone()), Ok(BlockStatus::SideBranch));
+            blockchain.new_signed_tx_and_added_mempool(&alice, "Carol".to_string(), 5);
+            assert_eq!(blockchain.mempool.len(), 1);
+            (blockchain.blocks.clone(), blockchain.mempool.transactions())
+        };
+
+        let mut reloaded = Blockchain::with_genesis_allocations(&allocations);
+        reloaded.attach_store(&dir).unwrap();
+        assert_eq!(reloaded.blocks, blocks);
+        assert_eq!(reloaded.tips().len(), 2);
+        let mut at_height_1 = reloaded.stored_hashes_at(1);
+        at_height_1.sort();
+        let mut expected = vec![blocks[1].hash.clone(), rival.blocks[1].hash.clone()];
+        expected.sort();
+        assert_eq!(at_height_1, expected);
+        assert_eq!(reloaded.balance_of("Bob"), 10);
+        assert_eq!(reloaded.balance_of("miner"), 2 * BLOCK_SUBSIDY);
+        assert_eq!(reloaded.mempool.transactions(), pending);
+        assert_eq!(reloaded.next_nonce(&alice.address), 2);
+
+        // o índice de transações gravado bate com o refeito a partir dos blocos
+        let confirmed = &blocks[1].transactions[1];
+        assert_eq!(reloaded.find_transaction(&confirmed.tx_hash()), Some(confirmed));
+        let saved = reloaded.store.as_ref().unwrap().lock().unwrap().load_tx_index().unwrap();
+        assert_eq!(saved, Some((blocks[2].hash.clone(), reloaded.tx_map.clone())));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_saved_tx_index_is_used_only_on_the_same_tip() {
+        let dir = std::env::temp_dir().join(format!("blockchainpow-txindex-{}", uuid::Uuid::new_v4()));
+        let tip = {
+            let mut blockchain = Blockchain::new();
+            blockchain.attach_store(&dir).unwrap();
+            blockchain.add_block("miner");
+            blockchain.add_block("miner");
+            blockchain.blocks[2].hash.clone()
+        };
+        let store_of = |blockchain: &Blockchain| Arc::clone(blockchain.store.as_ref().unwrap());
+
+        // marca o índice gravado: se ele aparecer depois do restart, não foi refeito
+        let mut reloaded = Blockchain::new();
+        reloaded.attach_store(&dir).unwrap();
+        let (_, mut saved) = store_of(&reloaded).lock().unwrap().load_tx_index().unwrap().unwrap();
+        assert_eq!(saved, reloaded.tx_map);
+        let marker = TxLocation { block_hash: tip.clone(), height: 2, position: 9 };
+        saved.insert("marcador".to_string(), marker.clone());
+        store_of(&reloaded).lock().unwrap().save_tx_index(&tip, &saved).unwrap();
+        drop(reloaded);
+
+        let mut reloaded = Blockchain::new();
+        reloaded.attach_store(&dir).unwrap();
+        assert_eq!(reloaded.transaction_location("marcador"), Some(&marker));
+
+        // gravado para outro topo: refeito a partir dos blocos e regravado
+        store_of(&reloaded).lock().unwrap().save_tx_index("outro", &saved).unwrap();
+        drop(reloaded);
+        let mut reloaded = Blockchain::new();
+        reloaded.attach_store(&dir).unwrap();
+        assert_eq!(reloaded.transaction_location("marcador"), None);
+        let (saved_tip, _) = store_of(&reloaded).lock().unwrap().load_tx_index().unwrap().unwrap();
+        assert_eq!(saved_tip, tip);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_tx_index_follows_network_blocks_and_reorgs() {
+        let alice = generate_wallet();
+        let genesis = Blockchain::with_genesis_allocations(&[(alice.address.clone(), 100)]);
+
+        let mut source = genesis.clone();
+        let tx = Transaction::new_signed(&alice, "Bob".to_string(), 10, 0).unwrap();
+        source.add_transaction_to_mempool(tx.clone()).unwrap();
+        source.add_block("miner");
+
+        // bloco vindo da rede também entra no índice
+        let mut local = genesis.clone();
+        assert_eq!(local.add_block_from_network(source.blocks[1].clone()), Ok(BlockStatus::Connected));
+        assert_eq!(
+            local.transaction_location(&tx.tx_hash()),
+            Some(&TxLocation { block_hash: source.blocks[1].hash.clone(), height: 1, position: 1 })
+        );
+        assert_eq!(local.find_transaction(&tx.tx_hash()), Some(&tx));
+        assert_eq!(local.transaction_proof(&tx.tx_hash()).unwrap().1, 1);
+
+        // desconectado num reorg: sai do índice e volta a ser pendente
+        let mut heavier = genesis.clone();
+        heavier.add_block("rival");
+        heavier.add_block("rival");
+        assert!(local.replace_chain_if_heavier(&heavier.blocks));
+        assert_eq!(local.transaction_location(&tx.tx_hash()), None);
+        assert!(local.transaction_proof(&tx.tx_hash()).is_none());
+        assert_eq!(local.find_transaction(&tx.tx_hash()), Some(&tx));
+
+        // desserializada, a chain já volta com ledger e índices refeitos
+        let decoded: Blockchain = serde_json::from_str(&serde_json::to_string(&local).unwrap()).unwrap();
+        assert_eq!(decoded.tx_map, local.tx_map);
+        assert_eq!(decoded.balance_of("rival"), 2 * BLOCK_SUBSIDY);
+        assert_eq!(decoded.chain_work(), local.chain_work());
+        assert_eq!(decoded.get_block(&heavier.blocks[2].hash), Some(&heavier.blocks[2]));
+        assert_eq!(decoded.find_transaction(&tx.tx_hash()), Some(&tx));
+    }
 }
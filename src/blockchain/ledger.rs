@@ -0,0 +1,358 @@
+use crate::blockchain::block::Block;
+use crate::blockchain::blockchain::BLOCK_SUBSIDY;
+use crate::errors::TransactionError;
+use crate::wallet::transaction::Transaction;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+/// Estado do mundo (endereço -> saldo, próximo nonce) derivado dos blocos da chain.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Ledger {
+    balances: HashMap<String, u64>,
+    nonces: HashMap<String, u64>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Ledger::default()
+    }
+
+    /// Reaplica todos os blocos a partir do gênese e devolve o estado resultante.
+    pub fn from_blocks(blocks: &[Block]) -> Result<Ledger, TransactionError> {
+        let mut ledger = Ledger::new();
+        for block in blocks {
+            ledger.apply_block(block)?;
+        }
+        Ok(ledger)
+    }
+
+    pub fn balance_of(&self, address: &str) -> u64 {
+        self.balances.get(address).copied().unwrap_or(0)
+    }
+
+    /// Nonce que a próxima transação de `address` precisa ter.
+    pub fn next_nonce(&self, address: &str) -> u64 {
+        self.nonces.get(address).copied().unwrap_or(0)
+    }
+
+    /// Conecta um bloco. As transações do gênese são alocações e só creditam
+    /// `to_address`; todo outro bloco começa com exatamente uma coinbase.
+    /// Se alguma transação falhar o ledger fica como estava.
+    pub fn apply_block(&mut self, block: &Block) -> Result<(), TransactionError> {
+        let mut next = self.clone();
+        if block.index == 0 {
+            for tx in &block.transactions {
+                next.credit(&tx.to_address, tx.amount)?;
+            }
+        } else {
+            check_coinbase(block)?;
+            let (coinbase, txs) = block.transactions.split_first().expect("coinbase checada acima");
+            next.credit(&coinbase.to_address, coinbase.amount)?;
+            for tx in txs {
+                next.apply_transaction(tx)?;
+            }
+        }
+        *self = next;
+        Ok(())
+    }
+
+    /// Desfaz `block`, que precisa ser o último aplicado: as transações voltam na
+    /// ordem inversa e os nonces dos remetentes voltam para os delas. Com isso um
+    /// reorg só mexe nos blocos desconectados, sem reprocessar a chain desde o
+    /// gênese. Se o estado não bater com o bloco nada muda.
+    pub fn undo_block(&mut self, block: &Block) -> Result<(), TransactionError> {
+        let mut previous = self.clone();
+        for tx in block.transactions.iter().rev() {
+            previous.take_back(&tx.to_address, tx.amount)?;
+            if block.index == 0 || tx.is_coinbase() {
+                continue;
+            }
+            if previous.next_nonce(&tx.from_address) != tx.nonce + 1 {
+                return Err(TransactionError::InvalidNonce {
+                    address: tx.from_address.clone(),
+                    expected: previous.next_nonce(&tx.from_address),
+                    got: tx.nonce + 1,
+                });
+            }
+            let cost = tx.total_cost().ok_or(TransactionError::InvalidAmount)?;
+            previous.credit(&tx.from_address, cost)?;
+            if tx.nonce == 0 {
+                previous.nonces.remove(&tx.from_address);
+            } else {
+                previous.nonces.insert(tx.from_address.clone(), tx.nonce);
+            }
+        }
+        *self = previous;
+        Ok(())
+    }
+
+    // debita o que um crédito tinha dado; a conta que fica zerada sai do mapa,
+    // como se o crédito nunca tivesse existido
+    fn take_back(&mut self, address: &str, amount: u64) -> Result<(), TransactionError> {
+        self.check_funds(address, amount)?;
+        self.debit(address, amount);
+        if self.balance_of(address) == 0 {
+            self.balances.remove(address);
+        }
+        Ok(())
+    }
+
+    /// Move `amount` de `from_address` para `to_address`, tira `fee` do remetente
+    /// (quem recolhe é a coinbase) e consome o nonce dele.
+    pub fn apply_transaction(&mut self, tx: &Transaction) -> Result<(), TransactionError> {
+        self.check_nonce(&tx.from_address, tx.nonce)?;
+        let cost = tx.total_cost().ok_or(TransactionError::InvalidAmount)?;
+        self.check_funds(&tx.from_address, cost)?;
+        self.debit(&tx.from_address, cost);
+        self.credit(&tx.to_address, tx.amount)?;
+        self.nonces.insert(tx.from_address.clone(), tx.nonce + 1);
+        Ok(())
+    }
+
+    /// Falha se `nonce` não for exatamente o próximo de `address` (sem replay, sem buraco).
+    pub fn check_nonce(&self, address: &str, nonce: u64) -> Result<(), TransactionError> {
+        let expected = self.next_nonce(address);
+        if nonce != expected {
+            return Err(TransactionError::InvalidNonce {
+                address: address.to_string(),
+                expected,
+                got: nonce,
+            });
+        }
+        Ok(())
+    }
+
+    /// Falha se `address` não tiver saldo para gastar `amount`.
+    pub fn check_funds(&self, address: &str, amount: u64) -> Result<(), TransactionError> {
+        let balance = self.balance_of(address);
+        if balance < amount {
+            return Err(TransactionError::InsufficientFunds {
+                address: address.to_string(),
+                balance,
+                amount,
+            });
+        }
+        Ok(())
+    }
+
+    fn debit(&mut self, address: &str, amount: u64) {
+        let balance = self.balances.entry(address.to_string()).or_insert(0);
+        *balance -= amount;
+    }
+
+    fn credit(&mut self, address: &str, amount: u64) -> Result<(), TransactionError> {
+        let balance = self.balances.entry(address.to_string()).or_insert(0);
+        *balance = balance
+            .checked_add(amount)
+            .ok_or(TransactionError::InvalidAmount)?;
+        Ok(())
+    }
+}
+
+/// Soma das taxas pagas pelas transações do bloco que não são a coinbase.
+pub fn block_fees(block: &Block) -> Result<u64, TransactionError> {
+    block
+        .transactions
+        .iter()
+        .filter(|tx| !tx.is_coinbase())
+        .try_fold(0u64, |acc, tx| acc.checked_add(tx.fee).ok_or(TransactionError::InvalidAmount))
+}
+
+/// A primeira transação tem que ser a única coinbase e não pode pedir mais que
+/// a recompensa do bloco (subsídio mais taxas).
+pub fn check_coinbase(block: &Block) -> Result<(), TransactionError> {
+    let coinbase = block
+        .transactions
+        .first()
+        .ok_or(TransactionError::InvalidCoinbase("Block has no coinbase".to_string()))?;
+    if !coinbase.is_coinbase() {
+        return Err(TransactionError::InvalidCoinbase("First transaction is not a coinbase".to_string()));
+    }
+    if block.transactions.iter().skip(1).any(|tx| tx.is_coinbase()) {
+        return Err(TransactionError::InvalidCoinbase("More than one coinbase in block".to_string()));
+    }
+    if coinbase.nonce != block.index {
+        return Err(TransactionError::InvalidCoinbase(format!(
+            "Coinbase nonce {} must be the block height {}",
+            coinbase.nonce, block.index
+        )));
+    }
+    let reward = BLOCK_SUBSIDY
+        .checked_add(block_fees(block)?)
+        .ok_or(TransactionError::InvalidAmount)?;
+    if coinbase.amount > reward {
+        return Err(TransactionError::InvalidCoinbase(format!(
+            "Coinbase claims {} but the reward is {}",
+            coinbase.amount, reward
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::pow::INITIAL_BITS;
+    use crate::wallet::wallet::generate_wallet;
+
+    #[test]
+    fn test_genesis_allocations_are_credited() {
+        let genesis = Block::new(0, vec![Transaction::genesis_allocation("Alice".to_string(), 100)], "0".to_string(), INITIAL_BITS);
+        let ledger = Ledger::from_blocks(&[genesis]).expect("genesis deve ser aplicado");
+
+        assert_eq!(ledger.balance_of("Alice"), 100);
+        assert_eq!(ledger.balance_of("Bob"), 0);
+    }
+
+    #[test]
+    fn test_transfer_moves_balance() {
+        let alice = generate_wallet();
+        let genesis = Block::new(0, vec![Transaction::genesis_allocation(alice.address.clone(), 100)], "0".to_string(), INITIAL_BITS);
+        let tx = Transaction::new_signed(&alice, "Bob".to_string(), 40, 0).unwrap();
+        let coinbase = Transaction::new_coinbase("Miner".to_string(), BLOCK_SUBSIDY, 1);
+        let block = Block::new(1, vec![coinbase, tx], genesis.hash.clone(), INITIAL_BITS);
+
+        let ledger = Ledger::from_blocks(&[genesis, block]).expect("saldo suficiente");
+        assert_eq!(ledger.balance_of(&alice.address), 60);
+        assert_eq!(ledger.balance_of("Bob"), 40);
+        assert_eq!(ledger.balance_of("Miner"), BLOCK_SUBSIDY);
+    }
+
+    #[test]
+    fn test_overspending_block_is_rejected_atomically() {
+        let alice = generate_wallet();
+        let genesis = Block::new(0, vec![Transaction::genesis_allocation(alice.address.clone(), 50)], "0".to_string(), INITIAL_BITS);
+        let mut ledger = Ledger::from_blocks(std::slice::from_ref(&genesis)).unwrap();
+
+        let tx1 = Transaction::new_signed(&alice, "Bob".to_string(), 30, 0).unwrap();
+        let tx2 = Transaction::new_signed(&alice, "Carol".to_string(), 30, 1).unwrap();
+        let coinbase = Transaction::new_coinbase("Miner".to_string(), BLOCK_SUBSIDY, 1);
+        let block = Block::new(1, vec![coinbase, tx1, tx2], genesis.hash.clone(), INITIAL_BITS);
+
+        let result = ledger.apply_block(&block);
+        assert!(matches!(result, Err(TransactionError::InsufficientFunds { .. })));
+
+        // nada deve ter sido aplicado
+        assert_eq!(ledger.balance_of(&alice.address), 50);
+        assert_eq!(ledger.balance_of("Bob"), 0);
+        assert_eq!(ledger.balance_of("Miner"), 0);
+    }
+
+    #[test]
+    fn test_coinbase_rules() {
+        let genesis = Block::new(0, vec![], "0".to_string(), INITIAL_BITS);
+        let ledger = Ledger::from_blocks(std::slice::from_ref(&genesis)).unwrap();
+
+        // sem coinbase
+        let block = Block::new(1, vec![], genesis.hash.clone(), INITIAL_BITS);
+        assert!(matches!(ledger.clone().apply_block(&block), Err(TransactionError::InvalidCoinbase(_))));
+
+        // coinbase pedindo mais do que a recompensa
+        let greedy = Transaction::new_coinbase("Miner".to_string(), BLOCK_SUBSIDY + 1, 1);
+        let block = Block::new(1, vec![greedy], genesis.hash.clone(), INITIAL_BITS);
+        assert!(matches!(ledger.clone().apply_block(&block), Err(TransactionError::InvalidCoinbase(_))));
+
+        // duas coinbases
+        let cb1 = Transaction::new_coinbase("Miner".to_string(), BLOCK_SUBSIDY, 1);
+        let cb2 = Transaction::new_coinbase("Other".to_string(), 1, 1);
+        let block = Block::new(1, vec![cb1.clone(), cb2.clone()], genesis.hash.clone(), INITIAL_BITS);
+        assert!(matches!(ledger.clone().apply_block(&block), Err(TransactionError::InvalidCoinbase(_))));
+
+        // coinbase fora da primeira posição
+        let tx = Transaction::genesis_allocation("Alice".to_string(), 1);
+        let block = Block::new(1, vec![tx, cb1.clone()], genesis.hash.clone(), INITIAL_BITS);
+        assert!(matches!(ledger.clone().apply_block(&block), Err(TransactionError::InvalidCoinbase(_))));
+
+        // coinbase com nonce diferente da altura
+        let wrong_height = Transaction::new_coinbase("Miner".to_string(), BLOCK_SUBSIDY, 7);
+        let block = Block::new(1, vec![wrong_height], genesis.hash.clone(), INITIAL_BITS);
+        assert!(matches!(ledger.clone().apply_block(&block), Err(TransactionError::InvalidCoinbase(_))));
+
+        let block = Block::new(1, vec![cb1], genesis.hash.clone(), INITIAL_BITS);
+        assert!(ledger.clone().apply_block(&block).is_ok());
+    }
+
+    #[test]
+    fn test_nonces_reject_replays_and_gaps() {
+        let alice = generate_wallet();
+        let genesis = Block::new(0, vec![Transaction::genesis_allocation(alice.address.clone(), 100)], "0".to_string(), INITIAL_BITS);
+        let mut ledger = Ledger::from_blocks(std::slice::from_ref(&genesis)).unwrap();
+        assert_eq!(ledger.next_nonce(&alice.address), 0);
+
+        let tx = Transaction::new_signed(&alice, "Bob".to_string(), 10, 0).unwrap();
+        ledger.apply_transaction(&tx).unwrap();
+        assert_eq!(ledger.next_nonce(&alice.address), 1);
+
+        // a mesma transação de novo
+        assert_eq!(
+            ledger.apply_transaction(&tx),
+            Err(TransactionError::InvalidNonce { address: alice.address.clone(), expected: 1, got: 0 })
+        );
+
+        // pulando um nonce
+        let gap = Transaction::new_signed(&alice, "Bob".to_string(), 10, 2).unwrap();
+        assert_eq!(
+            ledger.apply_transaction(&gap),
+            Err(TransactionError::InvalidNonce { address: alice.address.clone(), expected: 1, got: 2 })
+        );
+        assert_eq!(ledger.balance_of("Bob"), 10);
+    }
+
+    #[test]
+    fn test_undo_block_restores_the_previous_state() {
+        let alice = generate_wallet();
+        let genesis = Block::new(0, vec![Transaction::genesis_allocation(alice.address.clone(), 100)], "0".to_string(), INITIAL_BITS);
+        let block1 = Block::new(
+            1,
+            vec![
+                Transaction::new_coinbase("Miner".to_string(), BLOCK_SUBSIDY, 1),
+                Transaction::new_signed(&alice, "Bob".to_string(), 10, 0).unwrap(),
+            ],
+            genesis.hash.clone(),
+            INITIAL_BITS,
+        );
+        let block2 = Block::new(
+            2,
+            vec![
+                Transaction::new_coinbase("Miner".to_string(), BLOCK_SUBSIDY + 2, 2),
+                Transaction::new_signed_with_fee(&alice, "Carol".to_string(), 20, 2, 1).unwrap(),
+                Transaction::new_signed(&alice, "Bob".to_string(), 5, 2).unwrap(),
+            ],
+            block1.hash.clone(),
+            INITIAL_BITS,
+        );
+        let before = Ledger::from_blocks(&[genesis.clone(), block1.clone()]).unwrap();
+        let mut ledger = Ledger::from_blocks(&[genesis.clone(), block1.clone(), block2.clone()]).unwrap();
+
+        ledger.undo_block(&block2).unwrap();
+        assert_eq!(ledger, before);
+        ledger.undo_block(&block1).unwrap();
+        assert_eq!(ledger, Ledger::from_blocks(&[genesis]).unwrap());
+
+        // bloco que não foi o último aplicado: recusado sem mexer em nada
+        assert!(ledger.undo_block(&block2).is_err());
+        assert_eq!(ledger.balance_of(&alice.address), 100);
+    }
+
+    #[test]
+    fn test_fees_go_to_the_coinbase() {
+        let alice = generate_wallet();
+        let genesis = Block::new(0, vec![Transaction::genesis_allocation(alice.address.clone(), 100)], "0".to_string(), INITIAL_BITS);
+        let ledger = Ledger::from_blocks(std::slice::from_ref(&genesis)).unwrap();
+        let tx = Transaction::new_signed_with_fee(&alice, "Bob".to_string(), 40, 3, 0).unwrap();
+
+        // coinbase cobrando a taxa além do subsídio
+        let coinbase = Transaction::new_coinbase("Miner".to_string(), BLOCK_SUBSIDY + 3, 1);
+        let block = Block::new(1, vec![coinbase, tx.clone()], genesis.hash.clone(), INITIAL_BITS);
+        let mut next = ledger.clone();
+        next.apply_block(&block).expect("subsídio + taxas");
+        assert_eq!(next.balance_of(&alice.address), 57);
+        assert_eq!(next.balance_of("Bob"), 40);
+        assert_eq!(next.balance_of("Miner"), BLOCK_SUBSIDY + 3);
+
+        // um a mais que as taxas
+        let greedy = Transaction::new_coinbase("Miner".to_string(), BLOCK_SUBSIDY + 4, 1);
+        let block = Block::new(1, vec![greedy, tx], genesis.hash.clone(), INITIAL_BITS);
+        assert!(matches!(ledger.clone().apply_block(&block), Err(TransactionError::InvalidCoinbase(_))));
+    }
+}
@@ -0,0 +1,353 @@
+use crate::errors::TransactionError;
+use crate::wallet::transaction::{cmp_fee_rates, Transaction};
+use serde::{Serialize, Deserialize};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// Quantidade máxima padrão de transações pendentes.
+pub const DEFAULT_MAX_COUNT: usize = 5_000;
+/// Soma máxima padrão do tamanho canônico das pendentes, em bytes.
+pub const DEFAULT_MAX_BYTES: usize = 5 * 1024 * 1024;
+/// Depois deste tempo (segundos) sem entrar num bloco a transação é descartada.
+pub const DEFAULT_TTL: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct MempoolConfig {
+    pub max_count: usize,
+    pub max_bytes: usize,
+    pub ttl: i64,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        MempoolConfig {
+            max_count: DEFAULT_MAX_COUNT,
+            max_bytes: DEFAULT_MAX_BYTES,
+            ttl: DEFAULT_TTL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct MempoolEntry {
+    tx: Transaction,
+    tx_hash: String,
+    size: usize,
+    added_at: i64,
+}
+
+impl MempoolEntry {
+    // usa o tamanho guardado em vez de recodificar a transação
+    fn cmp_fee_rate(&self, other: &MempoolEntry) -> Ordering {
+        cmp_fee_rates((self.tx.fee, self.size), (other.tx.fee, other.size))
+    }
+}
+
+/// Transações pendentes em ordem de chegada, limitadas por quantidade e bytes.
+/// Quando enche, sai a de menor fee rate (e as seguintes do mesmo remetente,
+/// que ficariam com o nonce pulado).
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Mempool {
+    config: MempoolConfig,
+    entries: Vec<MempoolEntry>,
+    hashes: HashSet<String>,
+    bytes: usize,
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Self {
+        Mempool {
+            config,
+            ..Mempool::default()
+        }
+    }
+
+    pub fn config(&self) -> MempoolConfig {
+        self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Soma do tamanho canônico das pendentes.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn contains(&self, tx_hash: &str) -> bool {
+        self.hashes.contains(tx_hash)
+    }
+
+    pub fn get(&self, tx_hash: &str) -> Option<&Transaction> {
+        if !self.contains(tx_hash) {
+            return None;
+        }
+        self.entries.iter().find(|entry| entry.tx_hash == tx_hash).map(|entry| &entry.tx)
+    }
+
+    /// Pendentes em ordem de chegada.
+    pub fn iter(&self) -> impl Iterator<Item = &Transaction> {
+        self.entries.iter().map(|entry| &entry.tx)
+    }
+
+    pub fn transactions(&self) -> Vec<Transaction> {
+        self.iter().cloned().collect()
+    }
+
+    /// Pendentes em ordem de chegada, com o tamanho canônico já calculado.
+    pub fn iter_with_sizes(&self) -> impl Iterator<Item = (&Transaction, usize)> {
+        self.entries.iter().map(|entry| (&entry.tx, entry.size))
+    }
+
+    /// Insere `tx` e devolve o que foi despejado para abrir espaço. Se a própria
+    /// `tx` for a de menor fee rate com o mempool cheio, nada muda e volta MempoolFull.
+    pub fn insert(&mut self, tx: Transaction, now: i64) -> Result<Vec<Transaction>, TransactionError> {
+        let tx_hash = tx.tx_hash();
+        if self.contains(&tx_hash) {
+            return Err(TransactionError::Duplicate(tx_hash));
+        }
+        let entry = MempoolEntry { size: tx.size(), tx, tx_hash, added_at: now };
+
+        // decide quem sai antes de mexer em qualquer coisa
+        let marked = self.eviction_plan(&entry).ok_or(TransactionError::MempoolFull)?;
+        let mut position = 0;
+        let evicted = self.retain(|_| {
+            let keep = !marked[position];
+            position += 1;
+            keep
+        });
+        self.push(entry);
+        Ok(evicted)
+    }
+
+    // quais pendentes saem para `incoming` caber, pelas regras do despejo: menor
+    // fee rate primeiro, junto com as seguintes do mesmo remetente. None se a
+    // própria `incoming` acabaria despejada.
+    fn eviction_plan(&self, incoming: &MempoolEntry) -> Option<Vec<bool>> {
+        let n = self.entries.len();
+        let entry = |i: usize| if i == n { incoming } else { &self.entries[i] };
+        let mut marked = vec![false; n + 1];
+        let (mut count, mut bytes) = (n + 1, self.bytes + incoming.size);
+
+        while count > self.config.max_count || bytes > self.config.max_bytes {
+            // no empate sai a que chegou por último
+            let victim = (0..=n)
+                .rev()
+                .filter(|&i| !marked[i])
+                .reduce(|lowest, i| if entry(i).cmp_fee_rate(entry(lowest)).is_lt() { i } else { lowest })?;
+            let (from_address, nonce) = (&entry(victim).tx.from_address, entry(victim).tx.nonce);
+            for (i, is_marked) in marked.iter_mut().enumerate() {
+                let candidate = entry(i);
+                if !*is_marked && candidate.tx.from_address == *from_address && candidate.tx.nonce >= nonce {
+                    *is_marked = true;
+                    count -= 1;
+                    bytes -= candidate.size;
+                }
+            }
+        }
+
+        if marked[n] {
+            return None;
+        }
+        marked.truncate(n);
+        Some(marked)
+    }
+
+    /// Mantém só as transações para as quais `keep` devolve true; devolve as removidas.
+    pub fn retain<F>(&mut self, mut keep: F) -> Vec<Transaction>
+    where
+        F: FnMut(&Transaction) -> bool,
+    {
+        let (kept, removed): (Vec<MempoolEntry>, Vec<MempoolEntry>) =
+            std::mem::take(&mut self.entries).into_iter().partition(|entry| keep(&entry.tx));
+        for entry in &removed {
+            self.forget(entry);
+        }
+        self.entries = kept;
+        removed.into_iter().map(|entry| entry.tx).collect()
+    }
+
+    /// Devolve ao mempool transações de blocos desconectados num reorg. Elas entram
+    /// na frente das pendentes, que costumam depender delas (nonces seguintes).
+    /// Devolve o que foi despejado se o limite estourar.
+    pub fn readd_front(&mut self, txs: Vec<Transaction>, now: i64) -> Vec<Transaction> {
+        let pending = std::mem::take(&mut self.entries);
+        self.hashes.clear();
+        self.bytes = 0;
+
+        for tx in txs {
+            let tx_hash = tx.tx_hash();
+            if !self.contains(&tx_hash) {
+                self.push(MempoolEntry { size: tx.size(), tx, tx_hash, added_at: now });
+            }
+        }
+        for entry in pending {
+            if !self.contains(&entry.tx_hash) {
+                self.push(entry);
+            }
+        }
+
+        let mut evicted = Vec::new();
+        while self.is_over_capacity() {
+            let victim = self.lowest_fee_rate().expect("mempool acima do limite não está vazio");
+            evicted.extend(self.remove_with_descendants(victim));
+        }
+        evicted
+    }
+
+    /// Descarta o que passou do TTL, junto com as seguintes do mesmo remetente.
+    pub fn expire(&mut self, now: i64) -> Vec<Transaction> {
+        let mut expired = Vec::new();
+        while let Some(index) = self
+            .entries
+            .iter()
+            .position(|entry| now.saturating_sub(entry.added_at) >= self.config.ttl)
+        {
+            expired.extend(self.remove_with_descendants(index));
+        }
+        expired
+    }
+
+    fn push(&mut self, entry: MempoolEntry) {
+        self.bytes += entry.size;
+        self.hashes.insert(entry.tx_hash.clone());
+        self.entries.push(entry);
+    }
+
+    fn forget(&mut self, entry: &MempoolEntry) {
+        self.bytes -= entry.size;
+        self.hashes.remove(&entry.tx_hash);
+    }
+
+    fn is_over_capacity(&self) -> bool {
+        self.entries.len() > self.config.max_count || self.bytes > self.config.max_bytes
+    }
+
+    // menor fee rate; no empate sai a que chegou por último
+    fn lowest_fee_rate(&self) -> Option<usize> {
+        (0..self.entries.len()).rev().reduce(|lowest, i| {
+            if self.entries[i].cmp_fee_rate(&self.entries[lowest]).is_lt() {
+                i
+            } else {
+                lowest
+            }
+        })
+    }
+
+    fn remove_with_descendants(&mut self, index: usize) -> Vec<Transaction> {
+        let victim = &self.entries[index].tx;
+        let (from_address, nonce) = (victim.from_address.clone(), victim.nonce);
+        self.retain(|tx| tx.from_address != from_address || tx.nonce < nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::wallet::generate_wallet;
+
+    fn config(max_count: usize) -> MempoolConfig {
+        MempoolConfig { max_count, max_bytes: DEFAULT_MAX_BYTES, ttl: 100 }
+    }
+
+    #[test]
+    fn test_rejects_duplicates() {
+        let alice = generate_wallet();
+        let mut mempool = Mempool::new(config(10));
+        let tx = Transaction::new_signed(&alice, "Bob".to_string(), 10, 0).unwrap();
+
+        assert_eq!(mempool.insert(tx.clone(), 0), Ok(vec![]));
+        assert_eq!(mempool.insert(tx.clone(), 0), Err(TransactionError::Duplicate(tx.tx_hash())));
+        assert_eq!(mempool.len(), 1);
+        assert_eq!(mempool.bytes(), tx.size());
+        assert_eq!(mempool.get(&tx.tx_hash()), Some(&tx));
+    }
+
+    #[test]
+    fn test_full_mempool_evicts_lowest_fee_and_descendants() {
+        let alice = generate_wallet();
+        let bob = generate_wallet();
+        let carol = generate_wallet();
+        let mut mempool = Mempool::new(config(3));
+
+        let a0 = Transaction::new_signed_with_fee(&alice, "x".to_string(), 1, 1, 0).unwrap();
+        let a1 = Transaction::new_signed_with_fee(&alice, "x".to_string(), 1, 9, 1).unwrap();
+        let b0 = Transaction::new_signed_with_fee(&bob, "x".to_string(), 1, 5, 0).unwrap();
+        for tx in [a0.clone(), a1.clone(), b0.clone()] {
+            mempool.insert(tx, 0).unwrap();
+        }
+
+        // a0 é a mais barata: sai junto com a1, que dependia dela
+        let c0 = Transaction::new_signed_with_fee(&carol, "x".to_string(), 1, 3, 0).unwrap();
+        let evicted = mempool.insert(c0.clone(), 0).unwrap();
+        assert_eq!(evicted, vec![a0, a1]);
+        assert_eq!(mempool.transactions(), vec![b0, c0]);
+        assert!(mempool.bytes() > 0);
+    }
+
+    #[test]
+    fn test_cheapest_newcomer_is_refused_without_evicting() {
+        let alice = generate_wallet();
+        let bob = generate_wallet();
+        let mut mempool = Mempool::new(config(1));
+
+        let rich = Transaction::new_signed_with_fee(&alice, "x".to_string(), 1, 5, 0).unwrap();
+        mempool.insert(rich.clone(), 0).unwrap();
+
+        let cheap = Transaction::new_signed_with_fee(&bob, "x".to_string(), 1, 1, 0).unwrap();
+        assert_eq!(mempool.insert(cheap, 0), Err(TransactionError::MempoolFull));
+        assert_eq!(mempool.transactions(), vec![rich]);
+    }
+
+    #[test]
+    fn test_newcomer_that_depends_on_the_victim_is_refused() {
+        let alice = generate_wallet();
+        let bob = generate_wallet();
+        let mut mempool = Mempool::new(config(2));
+        let a0 = Transaction::new_signed_with_fee(&alice, "x".to_string(), 1, 1, 0).unwrap();
+        let b0 = Transaction::new_signed_with_fee(&bob, "x".to_string(), 1, 5, 0).unwrap();
+        mempool.insert(a0.clone(), 0).unwrap();
+        mempool.insert(b0.clone(), 0).unwrap();
+
+        // a1 paga bem, mas despejar a0 levaria a1 junto
+        let a1 = Transaction::new_signed_with_fee(&alice, "x".to_string(), 1, 9, 1).unwrap();
+        assert_eq!(mempool.insert(a1, 0), Err(TransactionError::MempoolFull));
+        assert_eq!(mempool.transactions(), vec![a0.clone(), b0.clone()]);
+        assert_eq!(mempool.bytes(), a0.size() + b0.size());
+    }
+
+    #[test]
+    fn test_expired_transactions_are_dropped() {
+        let alice = generate_wallet();
+        let mut mempool = Mempool::new(config(10));
+        let old = Transaction::new_signed(&alice, "x".to_string(), 1, 0).unwrap();
+        let child = Transaction::new_signed(&alice, "x".to_string(), 1, 1).unwrap();
+        mempool.insert(old.clone(), 0).unwrap();
+        mempool.insert(child.clone(), 50).unwrap();
+
+        assert!(mempool.expire(99).is_empty());
+        // a filha ainda está no prazo, mas ficaria com o nonce pulado
+        assert_eq!(mempool.expire(100), vec![old, child]);
+        assert!(mempool.is_empty());
+        assert_eq!(mempool.bytes(), 0);
+    }
+
+    #[test]
+    fn test_readd_front_keeps_nonce_order() {
+        let alice = generate_wallet();
+        let mut mempool = Mempool::new(config(10));
+        let a0 = Transaction::new_signed(&alice, "x".to_string(), 1, 0).unwrap();
+        let a1 = Transaction::new_signed(&alice, "x".to_string(), 1, 1).unwrap();
+        mempool.insert(a1.clone(), 0).unwrap();
+
+        // a0 voltou de um bloco desconectado; a1 já estava pendente
+        assert!(mempool.readd_front(vec![a0.clone(), a1.clone()], 10).is_empty());
+        assert_eq!(mempool.transactions(), vec![a0.clone(), a1.clone()]);
+        assert_eq!(mempool.bytes(), a0.size() + a1.size());
+    }
+}
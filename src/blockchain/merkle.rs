@@ -0,0 +1,206 @@
+use sha2::{Digest, Sha256};
+use serde::{Serialize, Deserialize};
+use crate::blockchain::block::BlockHeader;
+
+/// Raiz usada quando o bloco não tem transações.
+pub const EMPTY_MERKLE_ROOT: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Hash de um nó interno: sha256(esquerda || direita), sobre os bytes dos hashes.
+pub fn hash_pair(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().to_vec()
+}
+
+/// Calcula a raiz de Merkle sobre os `tx_hash` (hex) das transações.
+/// Em níveis com número ímpar de nós o último é duplicado, como no Bitcoin.
+pub fn merkle_root(tx_hashes: &[String]) -> String {
+    if tx_hashes.is_empty() {
+        return EMPTY_MERKLE_ROOT.to_string();
+    }
+
+    let mut level: Vec<Vec<u8>> = tx_hashes.iter().map(|h| leaf_bytes(h)).collect();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    hex::encode(&level[0])
+}
+
+pub fn next_level(level: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    level
+        .chunks(2)
+        .map(|pair| {
+            let left = &pair[0];
+            let right = pair.get(1).unwrap_or(left);
+            hash_pair(left, right)
+        })
+        .collect()
+}
+
+/// Um passo do caminho até a raiz: o hash irmão e de que lado ele fica.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct MerkleStep {
+    pub hash: String,
+    pub is_left: bool,
+}
+
+/// Prova de inclusão: a transação, a posição dela no bloco, quantas transações
+/// o bloco tem, o caminho de Merkle e o header do bloco.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TransactionProof {
+    pub tx_hash: String,
+    pub position: usize,
+    pub leaf_count: usize,
+    pub branch: Vec<MerkleStep>,
+    pub header: BlockHeader,
+}
+
+/// Monta o caminho de Merkle da folha `index`. None se o índice não existir.
+pub fn merkle_branch(tx_hashes: &[String], index: usize) -> Option<Vec<MerkleStep>> {
+    if index >= tx_hashes.len() {
+        return None;
+    }
+
+    let mut branch = Vec::new();
+    let mut level: Vec<Vec<u8>> = tx_hashes.iter().map(|h| leaf_bytes(h)).collect();
+    let mut position = index;
+    while level.len() > 1 {
+        let sibling_position = position ^ 1;
+        // sem irmão (nível ímpar) o nó é pareado consigo mesmo
+        let sibling = level.get(sibling_position).unwrap_or(&level[position]);
+        branch.push(MerkleStep {
+            hash: hex::encode(sibling),
+            is_left: sibling_position < position,
+        });
+        level = next_level(&level);
+        position /= 2;
+    }
+    Some(branch)
+}
+
+/// Sobe da folha `position` de uma árvore com `leaf_count` folhas até a raiz
+/// seguindo o caminho. O lado de cada irmão sai da posição, não do passo, e um
+/// nó só pode ser pareado consigo mesmo quando é o último de um nível ímpar:
+/// sem isso a cópia que a duplicação cria (uma posição fantasma depois da
+/// última) também teria prova. None se o caminho não tiver a forma da árvore.
+pub fn root_from_branch(tx_hash: &str, position: usize, leaf_count: usize, branch: &[MerkleStep]) -> Option<String> {
+    if position >= leaf_count {
+        return None;
+    }
+    let mut current = leaf_bytes(tx_hash);
+    let (mut position, mut width) = (position, leaf_count);
+    let mut steps = branch.iter();
+    while width > 1 {
+        let step = steps.next()?;
+        let sibling = leaf_bytes(&step.hash);
+        let is_left = position % 2 == 1;
+        let has_sibling = (position ^ 1) < width;
+        if step.is_left != is_left || (sibling == current) == has_sibling {
+            return None;
+        }
+        current = if is_left {
+            hash_pair(&sibling, &current)
+        } else {
+            hash_pair(&current, &sibling)
+        };
+        position /= 2;
+        width = width.div_ceil(2);
+    }
+    if steps.next().is_some() {
+        return None;
+    }
+    Some(hex::encode(current))
+}
+
+/// Verificação para clientes leves: o header precisa ter hash e PoW válidos
+/// e o caminho precisa levar da transação, na posição dita, até a merkle_root
+/// desse header.
+pub fn verify_transaction_proof(proof: &TransactionProof) -> bool {
+    proof.header.is_valid()
+        && root_from_branch(&proof.tx_hash, proof.position, proof.leaf_count, &proof.branch).as_ref()
+            == Some(&proof.header.merkle_root)
+}
+
+// tx_hash é sempre hex; se não for, usa os bytes da string para não entrar em pânico
+pub fn leaf_bytes(tx_hash: &str) -> Vec<u8> {
+    hex::decode(tx_hash).unwrap_or_else(|_| tx_hash.as_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h(data: &str) -> String {
+        hex::encode(Sha256::digest(data.as_bytes()))
+    }
+
+    #[test]
+    fn test_empty_and_single_leaf() {
+        assert_eq!(merkle_root(&[]), EMPTY_MERKLE_ROOT);
+
+        // com uma folha só, a raiz é a própria folha
+        let leaf = h("a");
+        assert_eq!(merkle_root(std::slice::from_ref(&leaf)), leaf);
+    }
+
+    #[test]
+    fn test_two_and_three_leaves() {
+        let (a, b, c) = (h("a"), h("b"), h("c"));
+        let ab = hash_pair(&leaf_bytes(&a), &leaf_bytes(&b));
+        assert_eq!(merkle_root(&[a.clone(), b.clone()]), hex::encode(&ab));
+
+        // ímpar: c é duplicado
+        let cc = hash_pair(&leaf_bytes(&c), &leaf_bytes(&c));
+        assert_eq!(merkle_root(&[a, b, c]), hex::encode(hash_pair(&ab, &cc)));
+    }
+
+    #[test]
+    fn test_branch_leads_to_root_for_every_leaf() {
+        for size in 1..=7 {
+            let leaves: Vec<String> = (0..size).map(|i| h(&i.to_string())).collect();
+            let root = merkle_root(&leaves);
+            for (i, leaf) in leaves.iter().enumerate() {
+                let branch = merkle_branch(&leaves, i).expect("índice existe");
+                assert_eq!(root_from_branch(leaf, i, size, &branch), Some(root.clone()), "folha {} de {}", i, size);
+            }
+            assert!(merkle_branch(&leaves, size).is_none());
+        }
+    }
+
+    #[test]
+    fn test_branch_does_not_prove_other_leaf() {
+        let leaves: Vec<String> = (0..4).map(|i| h(&i.to_string())).collect();
+        let root = merkle_root(&leaves);
+        let branch = merkle_branch(&leaves, 1).unwrap();
+        assert_ne!(root_from_branch(&leaves[2], 1, 4, &branch), Some(root.clone()));
+        // o caminho é da posição 1: dito de outra posição não fecha
+        assert_eq!(root_from_branch(&leaves[1], 0, 4, &branch), None);
+        assert_eq!(root_from_branch(&leaves[1], 1, 5, &branch), None);
+    }
+
+    #[test]
+    fn test_duplicated_last_leaf_has_no_phantom_proof() {
+        let (a, b, c) = (h("a"), h("b"), h("c"));
+        let root = merkle_root(&[a.clone(), b.clone(), c.clone()]);
+        let ab = hex::encode(hash_pair(&leaf_bytes(&a), &leaf_bytes(&b)));
+
+        // c de verdade, na posição 2 de 3
+        let branch = merkle_branch(&[a, b, c.clone()], 2).unwrap();
+        assert_eq!(root_from_branch(&c, 2, 3, &branch), Some(root.clone()));
+
+        // a cópia de c na posição 3 chega na mesma raiz se ninguém olhar a posição
+        let phantom = vec![
+            MerkleStep { hash: c.clone(), is_left: true },
+            MerkleStep { hash: ab, is_left: true },
+        ];
+        assert_eq!(root_from_branch(&c, 3, 3, &phantom), None);
+        assert_eq!(root_from_branch(&c, 3, 4, &phantom), None);
+    }
+
+    #[test]
+    fn test_root_depends_on_order() {
+        let (a, b) = (h("a"), h("b"));
+        assert_ne!(merkle_root(&[a.clone(), b.clone()]), merkle_root(&[b, a]));
+    }
+}
@@ -0,0 +1,326 @@
+use crate::blockchain::block::{calculate_hash, compute_merkle_root, Block};
+use crate::blockchain::pow::hash_meets_target;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// de quantas em quantas tentativas cada thread atualiza o contador e olha o cancelamento
+const ATTEMPT_BATCH: u64 = 1_000;
+
+/// Por quanto tempo um job encerrado continua consultável.
+pub const FINISHED_JOB_TTL: Duration = Duration::from_secs(10 * 60);
+/// Máximo de jobs encerrados no registro; acima disso saem os que terminaram primeiro.
+pub const MAX_FINISHED_JOBS: usize = 100;
+
+/// Estado compartilhado de uma mineração em andamento: quem minera lê o
+/// cancelamento, quem recebe um bloco concorrente chama `cancel`.
+#[derive(Debug)]
+pub struct MiningControl {
+    pub height: u64,
+    // pai do template: se deixar de ser o topo, o bloco minerado não serve mais
+    pub previous_hash: String,
+    cancelled: AtomicBool,
+    attempts: AtomicU64,
+}
+
+impl MiningControl {
+    pub fn new(height: u64, previous_hash: String) -> Self {
+        MiningControl {
+            height,
+            previous_hash,
+            cancelled: AtomicBool::new(false),
+            attempts: AtomicU64::new(0),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn attempts(&self) -> u64 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+}
+
+/// Resultado de `Miner::mine`. `block` é None se a mineração foi cancelada.
+#[derive(Debug)]
+pub struct MiningResult {
+    pub block: Option<Block>,
+    pub attempts: u64,
+    pub elapsed: Duration,
+}
+
+impl MiningResult {
+    /// Hashes por segundo durante esta mineração.
+    pub fn hashrate(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return self.attempts as f64;
+        }
+        self.attempts as f64 / secs
+    }
+}
+
+/// Minerador multithread: a thread `i` testa os nonces i, i + n, i + 2n...
+#[derive(Debug, Clone)]
+pub struct Miner {
+    pub threads: usize,
+}
+
+impl Miner {
+    /// `threads` fica entre 1 e o número de núcleos: mais threads que isso só
+    /// disputam a CPU, e um valor absurdo vindo de uma requisição não pode
+    /// esgotar o limite de threads do processo.
+    pub fn new(threads: usize) -> Self {
+        Miner { threads: threads.clamp(1, max_threads()) }
+    }
+
+    /// Usa todos os núcleos disponíveis.
+    pub fn with_available_parallelism() -> Self {
+        Miner::new(max_threads())
+    }
+
+    /// Procura um nonce para `template` até achar ou até `control` ser cancelado.
+    /// O nonce do template é ignorado; a busca começa do zero. Falha só se o
+    /// sistema não deixar criar as threads.
+    pub fn mine(&self, template: &Block, control: &MiningControl) -> io::Result<MiningResult> {
+        let started = Instant::now();
+        let mut template = template.clone();
+        template.merkle_root = compute_merkle_root(&template.transactions);
+
+        let found = AtomicBool::new(false);
+        let winner: Mutex<Option<Block>> = Mutex::new(None);
+        let step = self.threads as u64;
+
+        let spawned = std::thread::scope(|scope| {
+            for first_nonce in 0..step {
+                let template = &template;
+                let found = &found;
+                let winner = &winner;
+                let worker = std::thread::Builder::new().spawn_scoped(scope, move || {
+                    let mut nonce = first_nonce;
+                    let mut batch = 0;
+                    loop {
+                        let hash = calculate_hash(
+                            template.index,
+                            template.timestamp,
+                            &template.merkle_root,
+                            &template.previous_hash,
+                            template.bits,
+                            nonce,
+                        );
+                        batch += 1;
+
+                        if hash_meets_target(&hash, template.bits) {
+                            // só o primeiro que achar grava o bloco
+                            if !found.swap(true, Ordering::Relaxed) {
+                                let mut block = template.clone();
+                                block.nonce = nonce;
+                                block.hash = hash;
+                                *winner.lock().unwrap() = Some(block);
+                            }
+                            break;
+                        }
+
+                        if batch == ATTEMPT_BATCH {
+                            control.attempts.fetch_add(batch, Ordering::Relaxed);
+                            batch = 0;
+                            if found.load(Ordering::Relaxed) || control.is_cancelled() {
+                                break;
+                            }
+                        }
+
+                        nonce = match nonce.checked_add(step) {
+                            Some(next) => next,
+                            None => break,
+                        };
+                    }
+                    control.attempts.fetch_add(batch, Ordering::Relaxed);
+                });
+                if let Err(e) = worker {
+                    // as que já subiram param no próximo lote
+                    found.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+            }
+            Ok(())
+        });
+        spawned?;
+
+        Ok(MiningResult {
+            block: winner.into_inner().unwrap(),
+            attempts: control.attempts(),
+            elapsed: started.elapsed(),
+        })
+    }
+}
+
+/// Núcleos disponíveis (1 se não der para saber).
+pub fn max_threads() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Situação de um job de mineração em background.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum MiningStatus {
+    Running,
+    Found { index: u64, hash: String },
+    Cancelled,
+    // achou o bloco, mas a chain andou enquanto minerava
+    Stale { reason: String },
+    // o minerador não conseguiu rodar (por exemplo, sem threads)
+    Failed { reason: String },
+}
+
+/// Job criado pelo `POST /mine`; o handler consulta e o worker atualiza.
+#[derive(Debug)]
+pub struct MiningJob {
+    pub control: Arc<MiningControl>,
+    pub status: MiningStatus,
+    pub reward_address: String,
+    pub threads: usize,
+    started: Instant,
+    elapsed: Option<Duration>,
+}
+
+impl MiningJob {
+    pub fn new(control: Arc<MiningControl>, reward_address: String, threads: usize) -> Self {
+        MiningJob {
+            control,
+            status: MiningStatus::Running,
+            reward_address,
+            threads,
+            started: Instant::now(),
+            elapsed: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status == MiningStatus::Running
+    }
+
+    /// Encerra o job; o tempo decorrido para de contar aqui.
+    pub fn finish(&mut self, status: MiningStatus) {
+        self.status = status;
+        self.elapsed = Some(self.started.elapsed());
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed.unwrap_or_else(|| self.started.elapsed())
+    }
+
+    fn finished_at(&self) -> Option<Instant> {
+        self.elapsed.map(|elapsed| self.started + elapsed)
+    }
+}
+
+/// Tira do registro os jobs encerrados há mais de `ttl` e, se ainda sobrarem mais
+/// de `max_finished`, os que terminaram primeiro. Jobs em andamento ficam.
+pub fn prune_finished_jobs(jobs: &mut HashMap<String, MiningJob>, now: Instant, ttl: Duration, max_finished: usize) {
+    jobs.retain(|_, job| job.finished_at().is_none_or(|at| now.saturating_duration_since(at) < ttl));
+
+    let mut finished: Vec<(Instant, String)> = jobs
+        .iter()
+        .filter_map(|(id, job)| job.finished_at().map(|at| (at, id.clone())))
+        .collect();
+    if finished.len() > max_finished {
+        finished.sort_unstable();
+        for (_, id) in &finished[..finished.len() - max_finished] {
+            jobs.remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::blockchain::Blockchain;
+
+    #[test]
+    fn test_multithreaded_miner_finds_valid_block() {
+        let mut blockchain = Blockchain::new();
+        let template = blockchain.create_block_template("miner");
+        let control = MiningControl::new(template.index, template.previous_hash.clone());
+
+        let result = Miner::new(4).mine(&template, &control).unwrap();
+        assert!(result.attempts >= 1);
+        assert!(result.hashrate() > 0.0);
+        let block = result.block.expect("deve achar um nonce");
+        assert!(block.is_valid());
+
+        blockchain.connect_block(block).expect("bloco minerado deve conectar");
+        assert_eq!(blockchain.blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_cancelled_mining_returns_no_block() {
+        let blockchain = Blockchain::new();
+        let mut template = blockchain.create_block_template("miner");
+        // target impossível na prática: só o cancelamento encerra a busca
+        template.bits = 0x0301_0000;
+        let control = Arc::new(MiningControl::new(template.index, template.previous_hash.clone()));
+
+        let canceller = Arc::clone(&control);
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+
+        let result = Miner::new(2).mine(&template, &control).unwrap();
+        handle.join().unwrap();
+        assert!(result.block.is_none());
+        assert!(control.is_cancelled());
+        assert!(result.attempts > 0);
+    }
+
+    #[test]
+    fn test_thread_count_is_clamped() {
+        assert_eq!(Miner::new(0).threads, 1);
+        assert_eq!(Miner::new(usize::MAX).threads, max_threads());
+        assert_eq!(Miner::with_available_parallelism().threads, max_threads());
+    }
+
+    #[test]
+    fn test_finished_job_stops_the_clock() {
+        let mut job = MiningJob::new(Arc::new(MiningControl::new(1, "parent".to_string())), "miner".to_string(), 1);
+        assert!(job.is_running());
+
+        job.finish(MiningStatus::Cancelled);
+        assert!(!job.is_running());
+        let elapsed = job.elapsed();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(job.elapsed(), elapsed);
+    }
+
+    #[test]
+    fn test_finished_jobs_are_pruned() {
+        let new_job = || MiningJob::new(Arc::new(MiningControl::new(1, "parent".to_string())), "miner".to_string(), 1);
+        let mut jobs = HashMap::new();
+        jobs.insert("running".to_string(), new_job());
+        for id in ["first", "second", "third"] {
+            let mut job = new_job();
+            job.finish(MiningStatus::Cancelled);
+            jobs.insert(id.to_string(), job);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        // só cabem dois encerrados: sai o que terminou primeiro
+        prune_finished_jobs(&mut jobs, Instant::now(), FINISHED_JOB_TTL, 2);
+        let mut ids: Vec<&str> = jobs.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, ["running", "second", "third"]);
+
+        // passado o TTL sobra só o que ainda está rodando
+        let later = Instant::now() + FINISHED_JOB_TTL;
+        prune_finished_jobs(&mut jobs, later, FINISHED_JOB_TTL, MAX_FINISHED_JOBS);
+        assert_eq!(jobs.keys().collect::<Vec<_>>(), ["running"]);
+    }
+}
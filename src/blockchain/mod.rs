@@ -1,4 +1,13 @@
 pub mod block;
+pub mod block_index;
+pub mod block_store;
+#[allow(clippy::module_inception)]
 pub mod blockchain;
+pub mod ledger;
+pub mod mempool;
+pub mod merkle;
+pub mod miner;
 pub mod node;
-pub mod node_registry;
\ No newline at end of file
+pub mod node_registry;
+pub mod orphan_pool;
+pub mod pow;
\ No newline at end of file
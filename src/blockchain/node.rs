@@ -1,4 +1,4 @@
-use crate::blockchain::blockchain::Blockchain;
+use crate::blockchain::blockchain::{Blockchain, BlockStatus};
 use crate::blockchain::block::Block;
 use crate::wallet::transaction::Transaction;
 use crate::errors::TransactionError; // Ajustado para usar o módulo errors
@@ -74,8 +74,13 @@ impl Node {
     pub fn send_transaction(&mut self, to: &mut Node, tx: Result<Transaction, TransactionError>) {
         match tx {
             Ok(tx_ok) => {
-                self.blockchain.add_transaction_to_mempool(tx_ok.clone());
-                to.receive_transaction(tx_ok);
+                if let Err(e) = self.blockchain.add_transaction_to_mempool(tx_ok.clone()) {
+                    eprintln!("Transaction Rejected: {:?}", e);
+                    return;
+                }
+                if let Err(e) = to.receive_transaction(tx_ok) {
+                    eprintln!("Transaction Rejected by peer {}: {:?}", to.node_id, e);
+                }
             }
             Err(e) => {
                 eprintln!("Transaction Rejected: {:?}", e);
@@ -84,8 +89,8 @@ impl Node {
     }
 
     /// Recebe transação, coloca no mempool local
-    pub fn receive_transaction(&mut self, tx: Transaction) {
-        self.blockchain.add_transaction_to_mempool(tx);
+    pub fn receive_transaction(&mut self, tx: Transaction) -> Result<(), TransactionError> {
+        self.blockchain.add_transaction_to_mempool(tx)
     }
 
     /// Faz broadcast de um bloco para peers
@@ -96,19 +101,23 @@ impl Node {
         }
     }
 
-    /// Recebe bloco: se o índice bater com len() local, adiciona;
-    /// se for maior, tenta replace_chain_if_longer
+    /// Recebe bloco: se ele estende o nosso topo, adiciona; se o pai for
+    /// desconhecido, guarda como órfão e pede os ancestrais a quem anunciou
+    /// até a ancestralidade encostar na nossa chain
     pub fn receive_block(&mut self, block: Block, from_node: &Node) {
-        let local_len = self.blockchain.blocks.len();
-        let remote_index = block.index as usize;
-
-        if remote_index == local_len {
-            self.blockchain.add_block_from_network(block);
-        } else if remote_index > local_len {
-            let remote_chain = from_node.blockchain.clone();
-            self.blockchain.replace_chain_if_longer(&remote_chain);
+        let mut next = Some(block);
+        while let Some(block) = next.take() {
+            match self.blockchain.add_block_from_network(block) {
+                Ok(BlockStatus::Orphaned { missing_parent }) => {
+                    next = from_node.blockchain.get_block(&missing_parent).cloned();
+                    if next.is_none() {
+                        println!("Node {} não tem o bloco {}", from_node.node_id, missing_parent);
+                    }
+                }
+                Ok(status) => println!("Bloco recebido de {}: {:?}", from_node.node_id, status),
+                Err(e) => println!("Bloco de {} rejeitado: {}", from_node.node_id, e),
+            }
         }
-        // se for menor, ignora (é um fork mais curto)
     }
 
     #[allow(dead_code)]
@@ -132,11 +141,11 @@ impl Node {
             .signature
             .as_ref()
             .ok_or(TransactionError::InvalidTx("Missing signature".to_string()))?;
+        tx.check_address_binding()?;
 
-        // Transformar (from_address, to_address, amount) em um hash
-        let data_string = format!("{}|{}|{}", tx.from_address, tx.to_address, tx.amount);
+        // Transformar o payload canônico (from_address, to_address, amount) em um hash
         let mut hasher = Sha256::new();
-        hasher.update(data_string.as_bytes());
+        hasher.update(tx.signing_payload());
         let result = hasher.finalize();
 
         // Criar mensagem a partir do hash
@@ -161,6 +170,8 @@ impl Drop for Node {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blockchain::blockchain::BLOCK_SUBSIDY;
+    use crate::errors::BlockError;
     use crate::wallet::wallet::generate_wallet;
 
     /// Teste 1: usa IDs=10 e 11
@@ -173,14 +184,16 @@ mod tests {
         node2.peers = vec![10];
 
         let wallet1 = generate_wallet();
-        let tx1 = Transaction::new_signed(&wallet1, "Bob".to_string(), 30)
+        node1.blockchain = Blockchain::with_genesis_allocations(&[(wallet1.address.clone(), 100)]);
+        node2.blockchain = node1.blockchain.clone();
+        let tx1 = Transaction::new_signed(&wallet1, "Bob".to_string(), 30, 0)
             .expect("Failed to create the transaction");
 
         node1.send_transaction(&mut node2, Ok(tx1.clone()));
-        assert_eq!(node2.blockchain.pending_transactions.len(), 1);
-        let received_tx = &node2.blockchain.pending_transactions[0];
+        assert_eq!(node2.blockchain.mempool.len(), 1);
+        let received_tx = &node2.blockchain.mempool.transactions()[0];
         assert_eq!(*received_tx, tx1);
-        assert_eq!(node1.blockchain.pending_transactions[0], tx1);
+        assert_eq!(node1.blockchain.mempool.transactions()[0], tx1);
     }
 
     /// Teste 2: usa ID=12
@@ -190,25 +203,27 @@ mod tests {
 
         let wallet1 = generate_wallet();
         let wallet2 = generate_wallet();
+        node1.blockchain = Blockchain::with_genesis_allocations(&[(wallet1.address.clone(), 1000)]);
 
-        let tx1 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 50)
+        let tx1 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 50, 0)
             .expect("Failed to create the transaction");
-        let tx2 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 200)
+        let tx2 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 200, 1)
             .expect("Failed to create the transaction");
-        let tx_invalid_result = Transaction::new_signed(&wallet1, wallet2.address.clone(), 0);
+        let tx_invalid_result = Transaction::new_signed(&wallet1, wallet2.address.clone(), 0, 2);
 
         assert!(tx_invalid_result.is_err(), "Transação com amount=0 deveria falhar");
-        node1.receive_transaction(tx1.clone());
-        node1.receive_transaction(tx2.clone());
+        node1.receive_transaction(tx1.clone()).expect("tx1 válida");
+        node1.receive_transaction(tx2.clone()).expect("tx2 válida");
 
-        node1.blockchain.add_block();
+        node1.blockchain.add_block(&wallet2.address);
         let last_block = node1
             .blockchain
             .blocks
             .last()
             .expect("Haverá pelo menos o bloco gênese e o bloco minerado");
 
-        assert_eq!(last_block.transactions.len(), 2);
+        assert_eq!(last_block.transactions.len(), 3);
+        assert!(last_block.transactions[0].is_coinbase());
         assert!(last_block.transactions.contains(&tx1));
         assert!(last_block.transactions.contains(&tx2));
     }
@@ -220,32 +235,36 @@ mod tests {
         let node1 = Arc::new(Mutex::new(Node::new(1)));
         let node2 = Arc::new(Mutex::new(Node::new(2)));
 
+        let wallet1 = generate_wallet();
+        let wallet2 = generate_wallet();
+        let funded = Blockchain::with_genesis_allocations(&[(wallet1.address.clone(), 1000)]);
+
         {
             let mut n0 = node0.lock().unwrap();
             n0.peers = vec![1, 2];
+            n0.blockchain = funded.clone();
         }
         {
             let mut n1 = node1.lock().unwrap();
             n1.peers = vec![0, 2];
+            n1.blockchain = funded.clone();
         }
         {
             let mut n2 = node2.lock().unwrap();
             n2.peers = vec![0, 1];
+            n2.blockchain = funded.clone();
         }
 
-        let wallet1 = generate_wallet();
-        let wallet2 = generate_wallet();
-
-        let tx1 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 50)
+        let tx1 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 50, 0)
             .expect("Failed to create tx1");
-        let tx2 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 200)
+        let tx2 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 200, 1)
             .expect("Failed to create tx2");
 
         let last_block = {
             let mut n0 = node0.lock().unwrap();
-            n0.receive_transaction(tx1.clone());
-            n0.receive_transaction(tx2.clone());
-            n0.blockchain.add_block();
+            n0.receive_transaction(tx1.clone()).expect("tx1 válida");
+            n0.receive_transaction(tx2.clone()).expect("tx2 válida");
+            n0.blockchain.add_block(&wallet2.address);
             assert_eq!(n0.blockchain.blocks.len(), 2);
             n0.blockchain.blocks.last().unwrap().clone()
         };
@@ -259,7 +278,7 @@ mod tests {
             let n1 = node1.lock().unwrap();
             assert_eq!(n1.blockchain.blocks.len(), 2);
             let last_block_node1 = n1.blockchain.blocks.last().unwrap();
-            assert_eq!(last_block_node1.transactions.len(), 2);
+            assert_eq!(last_block_node1.transactions.len(), 3);
             assert!(last_block_node1.transactions.contains(&tx1));
             assert!(last_block_node1.transactions.contains(&tx2));
         }
@@ -268,7 +287,7 @@ mod tests {
             let n2 = node2.lock().unwrap();
             assert_eq!(n2.blockchain.blocks.len(), 2);
             let last_block_node2 = n2.blockchain.blocks.last().unwrap();
-            assert_eq!(last_block_node2.transactions.len(), 2);
+            assert_eq!(last_block_node2.transactions.len(), 3);
             assert!(last_block_node2.transactions.contains(&tx1));
             assert!(last_block_node2.transactions.contains(&tx2));
         }
@@ -282,9 +301,9 @@ mod tests {
 
         assert_eq!(node_short.blockchain.blocks.len(), 1);
 
-        node_long.blockchain.add_block();
-        node_long.blockchain.add_block();
-        node_long.blockchain.add_block();
+        node_long.blockchain.add_block("miner");
+        node_long.blockchain.add_block("miner");
+        node_long.blockchain.add_block("miner");
 
         assert_eq!(node_long.blockchain.blocks.len(), 4);
         assert_eq!(node_short.blockchain.blocks.len(), 1);
@@ -313,8 +332,9 @@ mod tests {
     #[test]
     fn test_fork_same_index_different_hash() {
         let mut node_a = Node::new(40);
+        let mut rival = node_a.blockchain.clone();
 
-        node_a.blockchain.add_block();
+        node_a.blockchain.add_block("miner");
         assert_eq!(node_a.blockchain.blocks.len(), 2);
 
         let block_normal = node_a
@@ -324,10 +344,42 @@ mod tests {
             .cloned()
             .expect("Deveria haver um bloco");
 
+        // mesmo índice, hash inventado: não passa nem da checagem do hash
         let mut block_fork = block_normal.clone();
         block_fork.transactions = vec![];
         block_fork.hash = "fake_hash_of_fork".to_string();
-        // Teste incompleto no original, mantido como está
+        assert_eq!(
+            node_a.blockchain.add_block_from_network(block_fork),
+            Err(BlockError::InvalidHash)
+        );
+
+        // fork de verdade: outro minerador na mesma altura, a partir do mesmo gênese
+        rival.add_block("rival");
+        let block_rival = rival.blocks[1].clone();
+        assert_eq!(block_rival.index, block_normal.index);
+        assert_ne!(block_rival.hash, block_normal.hash);
+
+        // empate em trabalho: o bloco fica guardado, a chain ativa não muda
+        assert_eq!(
+            node_a.blockchain.add_block_from_network(block_rival.clone()),
+            Ok(BlockStatus::SideBranch)
+        );
+        assert_eq!(node_a.blockchain.blocks.last().unwrap().hash, block_normal.hash);
+        let tips = node_a.blockchain.tips();
+        assert_eq!(tips.len(), 2);
+        assert!(tips.iter().all(|tip| tip.height == 1));
+
+        // o ramo lateral passa na frente: troca a chain ativa e o bloco antigo vira ponta lateral
+        rival.add_block("rival");
+        assert_eq!(
+            node_a.blockchain.add_block_from_network(rival.blocks[2].clone()),
+            Ok(BlockStatus::Reorganized)
+        );
+        assert_eq!(node_a.blockchain.blocks, rival.blocks);
+        let tips = node_a.blockchain.tips();
+        assert_eq!(tips[0].hash, rival.blocks[2].hash);
+        assert_eq!(tips[1].hash, block_normal.hash);
+        assert_eq!(node_a.blockchain.balance_of("miner"), 0);
     }
 
     /// Teste 6: ID=50
@@ -337,19 +389,22 @@ mod tests {
 
         let wallet1 = generate_wallet();
         let wallet2 = generate_wallet();
+        node.blockchain = Blockchain::with_genesis_allocations(&[(wallet1.address.clone(), 1000)]);
 
-        let tx1 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 50)
+        let tx1 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 50, 0)
             .expect("Failed to create tx1");
-        node.receive_transaction(tx1);
-        node.blockchain.add_block();
+        node.receive_transaction(tx1).expect("tx1 válida");
+        node.blockchain.add_block(&wallet2.address);
 
-        let tx2 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 100)
+        let tx2 = Transaction::new_signed(&wallet1, wallet2.address.clone(), 100, 1)
             .expect("Failed to create tx2");
-        node.receive_transaction(tx2);
-        node.blockchain.add_block();
+        node.receive_transaction(tx2).expect("tx2 válida");
+        node.blockchain.add_block(&wallet2.address);
 
         assert_eq!(node.blockchain.blocks.len(), 3);
         assert!(node.blockchain.is_valid());
+        assert_eq!(node.blockchain.balance_of(&wallet1.address), 850);
+        assert_eq!(node.blockchain.balance_of(&wallet2.address), 150 + 2 * BLOCK_SUBSIDY);
 
         let mut corrupt_block = node.blockchain.blocks[1].clone();
         corrupt_block.transactions.clear();
@@ -0,0 +1,149 @@
+use crate::blockchain::block::Block;
+use std::collections::{HashMap, VecDeque};
+
+/// Quantidade máxima padrão de blocos órfãos guardados.
+pub const DEFAULT_MAX_ORPHANS: usize = 100;
+
+/// Blocos que chegaram antes do pai. Ficam aqui até a ancestralidade chegar;
+/// quando enche, sai o mais antigo.
+#[derive(Debug, Clone)]
+pub struct OrphanPool {
+    max_orphans: usize,
+    blocks: HashMap<String, Block>,
+    // previous_hash -> hashes dos filhos que estão esperando esse pai
+    children: HashMap<String, Vec<String>>,
+    // ordem de chegada, para despejar o mais antigo
+    arrival: VecDeque<String>,
+}
+
+impl Default for OrphanPool {
+    fn default() -> Self {
+        OrphanPool::new(DEFAULT_MAX_ORPHANS)
+    }
+}
+
+impl OrphanPool {
+    pub fn new(max_orphans: usize) -> Self {
+        OrphanPool {
+            max_orphans: max_orphans.max(1),
+            blocks: HashMap::new(),
+            children: HashMap::new(),
+            arrival: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    /// Guarda `block` e devolve os órfãos despejados para abrir espaço.
+    /// Um bloco que já está no pool é ignorado.
+    pub fn insert(&mut self, block: Block) -> Vec<Block> {
+        if self.contains(&block.hash) {
+            return Vec::new();
+        }
+
+        let mut evicted = Vec::new();
+        while self.blocks.len() >= self.max_orphans {
+            let Some(oldest) = self.arrival.front().cloned() else {
+                break;
+            };
+            evicted.extend(self.remove(&oldest));
+        }
+
+        self.children
+            .entry(block.previous_hash.clone())
+            .or_default()
+            .push(block.hash.clone());
+        self.arrival.push_back(block.hash.clone());
+        self.blocks.insert(block.hash.clone(), block);
+        evicted
+    }
+
+    pub fn remove(&mut self, hash: &str) -> Option<Block> {
+        let block = self.blocks.remove(hash)?;
+        if let Some(siblings) = self.children.get_mut(&block.previous_hash) {
+            siblings.retain(|h| h != hash);
+            if siblings.is_empty() {
+                self.children.remove(&block.previous_hash);
+            }
+        }
+        self.arrival.retain(|h| h != hash);
+        Some(block)
+    }
+
+    /// Primeiro ancestral de `hash` que está no pool: o pai dele é o bloco que
+    /// falta pedir (ou que já temos na chain).
+    pub fn root_of(&self, hash: &str) -> Option<&Block> {
+        let mut current = self.blocks.get(hash)?;
+        // o limite evita laço infinito com hashes forjados que se apontam
+        for _ in 0..self.blocks.len() {
+            match self.blocks.get(&current.previous_hash) {
+                Some(parent) => current = parent,
+                None => break,
+            }
+        }
+        Some(current)
+    }
+
+    /// Tira do pool os órfãos que esperavam `parent_hash`, em ordem de chegada.
+    pub fn take_children(&mut self, parent_hash: &str) -> Vec<Block> {
+        let hashes = self.children.get(parent_hash).cloned().unwrap_or_default();
+        hashes.iter().filter_map(|hash| self.remove(hash)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(index: u64, previous_hash: &str) -> Block {
+        Block::new(index, vec![], previous_hash.to_string(), crate::blockchain::pow::INITIAL_BITS)
+    }
+
+    #[test]
+    fn test_oldest_orphan_is_evicted_when_full() {
+        let mut pool = OrphanPool::new(2);
+        let a = block(5, "x");
+        let b = block(6, "y");
+        let c = block(7, "z");
+
+        assert!(pool.insert(a.clone()).is_empty());
+        assert!(pool.insert(b.clone()).is_empty());
+        assert_eq!(pool.insert(c.clone()), vec![a.clone()]);
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains(&a.hash));
+        assert!(pool.contains(&b.hash) && pool.contains(&c.hash));
+    }
+
+    #[test]
+    fn test_root_and_children_follow_ancestry() {
+        let mut pool = OrphanPool::new(10);
+        let first = block(3, "missing");
+        let second = block(4, &first.hash);
+        let third = block(5, &second.hash);
+        // chegam fora de ordem
+        pool.insert(third.clone());
+        pool.insert(first.clone());
+        pool.insert(second.clone());
+
+        let root = pool.root_of(&third.hash).unwrap();
+        assert_eq!(root.hash, first.hash);
+        assert_eq!(root.previous_hash, "missing");
+
+        assert!(pool.take_children("unknown").is_empty());
+        assert_eq!(pool.take_children("missing"), vec![first]);
+        assert_eq!(pool.take_children(&second.previous_hash), vec![second.clone()]);
+        assert_eq!(pool.root_of(&third.hash).unwrap().previous_hash, second.hash);
+        assert_eq!(pool.len(), 1);
+    }
+}
@@ -0,0 +1,335 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Target mais fácil permitido (equivalente ao regtest do Bitcoin).
+pub const POW_LIMIT_BITS: u32 = 0x207f_ffff;
+
+/// Target inicial da chain: hash <= 2^244, mais ou menos 3 zeros hex no início.
+pub const INITIAL_BITS: u32 = 0x1f10_0000;
+
+/// Inteiro sem sinal de 256 bits, usado para comparar hashes com o target.
+/// Limbs em little-endian (`0` é o menos significativo).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct U256([u64; 4]);
+
+impl U256 {
+    pub const ZERO: U256 = U256([0, 0, 0, 0]);
+    pub const MAX: U256 = U256([u64::MAX; 4]);
+
+    pub fn from_u64(value: u64) -> U256 {
+        U256([value, 0, 0, 0])
+    }
+
+    pub fn from_be_bytes(bytes: &[u8; 32]) -> U256 {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let start = 32 - (i + 1) * 8;
+            let mut chunk = [0u8; 8];
+            chunk.copy_from_slice(&bytes[start..start + 8]);
+            *limb = u64::from_be_bytes(chunk);
+        }
+        U256(limbs)
+    }
+
+    pub fn to_be_bytes(self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.0.iter().enumerate() {
+            let start = 32 - (i + 1) * 8;
+            bytes[start..start + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Lê um hash em hex (64 caracteres). Retorna None se não for um hash válido.
+    pub fn from_hex(hash: &str) -> Option<U256> {
+        let bytes = hex::decode(hash).ok()?;
+        let array: [u8; 32] = bytes.try_into().ok()?;
+        Some(U256::from_be_bytes(&array))
+    }
+
+    pub fn to_hex(self) -> String {
+        hex::encode(self.to_be_bytes())
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|&limb| limb == 0)
+    }
+
+    pub fn low_u64(&self) -> u64 {
+        self.0[0]
+    }
+
+    /// Número de bits significativos.
+    pub fn bits(&self) -> u32 {
+        for i in (0..4).rev() {
+            if self.0[i] != 0 {
+                return i as u32 * 64 + (64 - self.0[i].leading_zeros());
+            }
+        }
+        0
+    }
+
+    pub fn shl(self, shift: u32) -> U256 {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        let limb_shift = (shift / 64) as usize;
+        let bit_shift = shift % 64;
+        let mut out = [0u64; 4];
+        for i in (limb_shift..4).rev() {
+            let src = i - limb_shift;
+            out[i] = self.0[src] << bit_shift;
+            if bit_shift > 0 && src > 0 {
+                out[i] |= self.0[src - 1] >> (64 - bit_shift);
+            }
+        }
+        U256(out)
+    }
+
+    pub fn shr(self, shift: u32) -> U256 {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        let limb_shift = (shift / 64) as usize;
+        let bit_shift = shift % 64;
+        let mut out = [0u64; 4];
+        for (i, limb) in out.iter_mut().enumerate().take(4 - limb_shift) {
+            let src = i + limb_shift;
+            *limb = self.0[src] >> bit_shift;
+            if bit_shift > 0 && src + 1 < 4 {
+                *limb |= self.0[src + 1] << (64 - bit_shift);
+            }
+        }
+        U256(out)
+    }
+
+    pub fn checked_add(self, other: U256) -> Option<U256> {
+        let mut out = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = c1 || c2;
+        }
+        if carry { None } else { Some(U256(out)) }
+    }
+
+    pub fn saturating_add(self, other: U256) -> U256 {
+        self.checked_add(other).unwrap_or(U256::MAX)
+    }
+
+    /// Subtração; satura em zero.
+    pub fn saturating_sub(self, other: U256) -> U256 {
+        if self <= other {
+            return U256::ZERO;
+        }
+        let mut out = [0u64; 4];
+        let mut borrow = false;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let (diff, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            *limb = diff;
+            borrow = b1 || b2;
+        }
+        U256(out)
+    }
+
+    pub fn checked_mul_u64(self, factor: u64) -> Option<U256> {
+        let mut out = [0u64; 4];
+        let mut carry: u128 = 0;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let product = self.0[i] as u128 * factor as u128 + carry;
+            *limb = product as u64;
+            carry = product >> 64;
+        }
+        if carry != 0 { None } else { Some(U256(out)) }
+    }
+
+    /// Multiplicação por u64; satura em U256::MAX.
+    pub fn saturating_mul_u64(self, factor: u64) -> U256 {
+        self.checked_mul_u64(factor).unwrap_or(U256::MAX)
+    }
+
+    pub fn div_u64(self, divisor: u64) -> U256 {
+        assert!(divisor != 0, "divisão por zero");
+        let mut out = [0u64; 4];
+        let mut remainder: u128 = 0;
+        for i in (0..4).rev() {
+            let current = (remainder << 64) | self.0[i] as u128;
+            out[i] = (current / divisor as u128) as u64;
+            remainder = current % divisor as u128;
+        }
+        U256(out)
+    }
+
+    /// Divisão longa bit a bit.
+    pub fn div(self, divisor: U256) -> U256 {
+        assert!(!divisor.is_zero(), "divisão por zero");
+        if self < divisor {
+            return U256::ZERO;
+        }
+        let mut quotient = U256::ZERO;
+        let mut remainder = U256::ZERO;
+        for i in (0..self.bits()).rev() {
+            remainder = remainder.shl(1);
+            if self.bit(i) {
+                remainder.0[0] |= 1;
+            }
+            if remainder >= divisor {
+                remainder = remainder.saturating_sub(divisor);
+                quotient.0[(i / 64) as usize] |= 1 << (i % 64);
+            }
+        }
+        quotient
+    }
+
+    pub fn not(self) -> U256 {
+        U256([!self.0[0], !self.0[1], !self.0[2], !self.0[3]])
+    }
+
+    fn bit(&self, index: u32) -> bool {
+        (self.0[(index / 64) as usize] >> (index % 64)) & 1 == 1
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for i in (0..4).rev() {
+            match self.0[i].cmp(&other.0[i]) {
+                Ordering::Equal => continue,
+                ord => return ord,
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", self.to_hex())
+    }
+}
+
+/// Decodifica o formato compacto ("bits"): 1 byte de expoente + 3 de mantissa.
+/// Mantissa negativa (bit 0x00800000) ou overflow retornam None.
+pub fn target_from_bits(bits: u32) -> Option<U256> {
+    let exponent = bits >> 24;
+    let mantissa = bits & 0x007f_ffff;
+    if bits & 0x0080_0000 != 0 {
+        return None;
+    }
+    let target = if exponent <= 3 {
+        U256::from_u64((mantissa >> (8 * (3 - exponent))) as u64)
+    } else {
+        let shift = 8 * (exponent - 3);
+        let base = U256::from_u64(mantissa as u64);
+        if mantissa != 0 && base.bits() + shift > 256 {
+            return None;
+        }
+        base.shl(shift)
+    };
+    Some(target)
+}
+
+/// Codifica um target no formato compacto (perde precisão abaixo dos 3 bytes mais altos).
+pub fn bits_from_target(target: U256) -> u32 {
+    let mut size = target.bits().div_ceil(8);
+    let mut compact = if size <= 3 {
+        (target.low_u64() << (8 * (3 - size))) as u32
+    } else {
+        target.shr(8 * (size - 3)).low_u64() as u32
+    };
+    // se o bit de sinal ficaria ligado, desloca mais um byte
+    if compact & 0x0080_0000 != 0 {
+        compact >>= 8;
+        size += 1;
+    }
+    compact | (size << 24)
+}
+
+/// Trabalho esperado para achar um hash <= target: 2^256 / (target + 1).
+/// Calculado como (!target / (target + 1)) + 1 para caber em 256 bits.
+pub fn work_from_bits(bits: u32) -> U256 {
+    match target_from_bits(bits) {
+        Some(target) if !target.is_zero() => match target.checked_add(U256::from_u64(1)) {
+            Some(divisor) => target.not().div(divisor).saturating_add(U256::from_u64(1)),
+            None => U256::from_u64(1),
+        },
+        _ => U256::ZERO,
+    }
+}
+
+/// Compara numericamente o hash (hex) com o target codificado em `bits`.
+pub fn hash_meets_target(hash: &str, bits: u32) -> bool {
+    match (U256::from_hex(hash), target_from_bits(bits)) {
+        (Some(value), Some(target)) => !target.is_zero() && value <= target,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_roundtrip() {
+        for bits in [INITIAL_BITS, POW_LIMIT_BITS, 0x1d00_ffff, 0x0312_3456, 0x2001_0000] {
+            let target = target_from_bits(bits).expect("bits válidos");
+            assert_eq!(bits_from_target(target), bits, "roundtrip de {:#x}", bits);
+        }
+    }
+
+    #[test]
+    fn test_initial_bits_is_2_pow_244() {
+        let target = target_from_bits(INITIAL_BITS).unwrap();
+        assert_eq!(target, U256::from_u64(1).shl(244));
+        assert!(target < target_from_bits(POW_LIMIT_BITS).unwrap());
+    }
+
+    #[test]
+    fn test_negative_or_overflowing_bits_are_rejected() {
+        assert!(target_from_bits(0x0480_0000).is_none());
+        assert!(target_from_bits(0xff12_3456).is_none());
+        assert!(!hash_meets_target(&"00".repeat(32), 0x0480_0000));
+    }
+
+    #[test]
+    fn test_hash_meets_target_is_numeric() {
+        let bits = 0x2001_0000; // 2^248
+        let limit = U256::from_u64(1).shl(248);
+        assert!(hash_meets_target(&limit.to_hex(), bits));
+        assert!(!hash_meets_target(&limit.checked_add(U256::from_u64(1)).unwrap().to_hex(), bits));
+        assert!(hash_meets_target(&"00".repeat(32), bits));
+        assert!(!hash_meets_target("nao-e-hex", bits));
+    }
+
+    #[test]
+    fn test_work_from_bits() {
+        // target 2^244 => floor(2^256 / (2^244 + 1)) = 2^12 - 1
+        assert_eq!(work_from_bits(INITIAL_BITS), U256::from_u64((1 << 12) - 1));
+        // metade do target => (quase) o dobro do trabalho
+        assert_eq!(work_from_bits(0x1f08_0000), U256::from_u64((1 << 13) - 1));
+        assert!(work_from_bits(POW_LIMIT_BITS) < work_from_bits(INITIAL_BITS));
+        assert_eq!(work_from_bits(0x0480_0000), U256::ZERO);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = U256::from_u64(u64::MAX);
+        let b = a.checked_add(U256::from_u64(1)).unwrap();
+        assert_eq!(b, U256::from_u64(1).shl(64));
+        assert_eq!(b.shr(64), U256::from_u64(1));
+        assert_eq!(b.saturating_sub(U256::from_u64(1)), a);
+        assert_eq!(a.saturating_mul_u64(4).div_u64(4), a);
+        assert_eq!(U256::MAX.saturating_mul_u64(2), U256::MAX);
+        assert_eq!(U256::from_u64(1).shl(200).div(U256::from_u64(1).shl(100)), U256::from_u64(1).shl(100));
+        assert_eq!(U256::from_u64(100).div(U256::from_u64(7)), U256::from_u64(14));
+    }
+}
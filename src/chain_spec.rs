@@ -0,0 +1,202 @@
+use crate::blockchain::block::Block;
+use crate::blockchain::pow::{target_from_bits, INITIAL_BITS, POW_LIMIT_BITS};
+use crate::errors::ChainSpecError;
+use crate::wallet::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Timestamp fixo do gênese da rede padrão (2025-01-01 00:00:00 UTC).
+pub const DEFAULT_GENESIS_TIMESTAMP: i64 = 1_735_689_600;
+pub const DEFAULT_NETWORK: &str = "devnet";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Allocation {
+    pub address: String,
+    pub amount: u64,
+}
+
+/// Hash conhecido de um bloco: toda chain aceita precisa passar por ele.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Checkpoint {
+    pub height: u64,
+    pub hash: String,
+}
+
+/// Parâmetros que definem uma rede: nós com a mesma spec montam o mesmo gênese.
+///
+/// ```json
+/// {
+///   "network": "testnet",
+///   "genesis_timestamp": 1735689600,
+///   "initial_bits": 521142272,
+///   "allocations": [{ "address": "ab12...", "amount": 1000 }],
+///   "genesis_hash": "00f3...",
+///   "checkpoints": [{ "height": 1000, "hash": "0000..." }]
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChainSpec {
+    pub network: String,
+    pub genesis_timestamp: i64,
+    pub initial_bits: u32,
+    #[serde(default)]
+    pub allocations: Vec<Allocation>,
+    // se presente, o nó se recusa a subir com um gênese diferente
+    #[serde(default)]
+    pub genesis_hash: Option<String>,
+    #[serde(default)]
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        ChainSpec {
+            network: DEFAULT_NETWORK.to_string(),
+            genesis_timestamp: DEFAULT_GENESIS_TIMESTAMP,
+            initial_bits: INITIAL_BITS,
+            allocations: Vec::new(),
+            genesis_hash: None,
+            checkpoints: Vec::new(),
+        }
+    }
+}
+
+impl ChainSpec {
+    /// Lê a spec de um arquivo JSON e confere os campos.
+    pub fn load(path: impl AsRef<Path>) -> Result<ChainSpec, ChainSpecError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| ChainSpecError::Io(format!("{}: {}", path.display(), e)))?;
+        ChainSpec::from_json(&contents)
+    }
+
+    pub fn from_json(json: &str) -> Result<ChainSpec, ChainSpecError> {
+        let spec: ChainSpec = serde_json::from_str(json).map_err(|e| ChainSpecError::Parse(e.to_string()))?;
+        spec.check()?;
+        Ok(spec)
+    }
+
+    /// Nome da rede presente, target válido e não mais fácil que o limite da rede,
+    /// sem alocações nem checkpoints repetidos.
+    pub fn check(&self) -> Result<(), ChainSpecError> {
+        if self.network.is_empty() {
+            return Err(ChainSpecError::Parse("network name is empty".to_string()));
+        }
+        let target = target_from_bits(self.initial_bits).ok_or(ChainSpecError::InvalidBits(self.initial_bits))?;
+        let limit = target_from_bits(POW_LIMIT_BITS).expect("POW_LIMIT_BITS é válido");
+        if target.is_zero() || target > limit {
+            return Err(ChainSpecError::InvalidBits(self.initial_bits));
+        }
+        let mut heights = HashSet::new();
+        if let Some(cp) = self.checkpoints.iter().find(|cp| !heights.insert(cp.height)) {
+            return Err(ChainSpecError::Parse(format!("duplicate checkpoint at height {}", cp.height)));
+        }
+        // duas alocações iguais dariam transações com o mesmo tx_hash no gênese
+        let mut addresses = HashSet::new();
+        if let Some(allocation) = self.allocations.iter().find(|a| !addresses.insert(&a.address)) {
+            return Err(ChainSpecError::Parse(format!("duplicate allocation for {}", allocation.address)));
+        }
+        Ok(())
+    }
+
+    /// Gênese determinístico: mesma rede, timestamp, bits e alocações dão sempre o
+    /// mesmo hash. O nome da rede entra no header como `previous_hash` do gênese,
+    /// então specs que só diferem no nome montam gêneses diferentes.
+    pub fn genesis_block(&self) -> Block {
+        let allocation_txs = self
+            .allocations
+            .iter()
+            .map(|allocation| Transaction::genesis_allocation(allocation.address.clone(), allocation.amount))
+            .collect();
+        let network_id = hex::encode(Sha256::digest(self.network.as_bytes()));
+        let mut genesis = Block::new(0, allocation_txs, network_id, self.initial_bits);
+        genesis.timestamp = self.genesis_timestamp;
+        genesis.hash = genesis.header_hash();
+        genesis
+    }
+
+    /// Confere o hash do gênese montado contra o `genesis_hash` da spec e contra
+    /// um checkpoint na altura 0, se houver.
+    pub fn check_genesis(&self, genesis: &Block) -> Result<(), ChainSpecError> {
+        let genesis_checkpoint = self.checkpoints.iter().find(|cp| cp.height == 0).map(|cp| &cp.hash);
+        for expected in self.genesis_hash.iter().chain(genesis_checkpoint) {
+            if *expected != genesis.hash {
+                return Err(ChainSpecError::GenesisMismatch {
+                    expected: expected.clone(),
+                    got: genesis.hash.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genesis_is_deterministic() {
+        let spec = ChainSpec {
+            allocations: vec![Allocation { address: "alice".to_string(), amount: 100 }],
+            ..ChainSpec::default()
+        };
+        let a = spec.genesis_block();
+        let b = spec.genesis_block();
+        assert_eq!(a, b);
+        assert_eq!(a.timestamp, DEFAULT_GENESIS_TIMESTAMP);
+        assert_ne!(a.hash, ChainSpec::default().genesis_block().hash);
+
+        // só o nome da rede muda: o gênese também
+        let other_network = ChainSpec { network: "testnet".to_string(), ..spec.clone() };
+        assert_ne!(other_network.genesis_block().hash, a.hash);
+    }
+
+    #[test]
+    fn test_parse_and_check_genesis_hash() {
+        let json = r#"{"network": "testnet", "genesis_timestamp": 1700000000, "initial_bits": 521142272}"#;
+        let spec = ChainSpec::from_json(json).unwrap();
+        assert_eq!(spec.network, "testnet");
+        assert!(spec.allocations.is_empty());
+        let genesis = spec.genesis_block();
+        assert_eq!(spec.check_genesis(&genesis), Ok(()));
+
+        let pinned = ChainSpec { genesis_hash: Some("00ff".to_string()), ..spec };
+        assert_eq!(
+            pinned.check_genesis(&genesis),
+            Err(ChainSpecError::GenesisMismatch { expected: "00ff".to_string(), got: genesis.hash.clone() })
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_at_genesis_must_match() {
+        let genesis = ChainSpec::default().genesis_block();
+        let spec = ChainSpec {
+            checkpoints: vec![Checkpoint { height: 0, hash: genesis.hash.clone() }],
+            ..ChainSpec::default()
+        };
+        assert_eq!(spec.check_genesis(&genesis), Ok(()));
+
+        let json = r#"{"network": "x", "genesis_timestamp": 0, "initial_bits": 521142272,
+            "checkpoints": [{"height": 0, "hash": "aa"}, {"height": 0, "hash": "bb"}]}"#;
+        assert!(matches!(ChainSpec::from_json(json), Err(ChainSpecError::Parse(_))));
+    }
+
+    #[test]
+    fn test_rejects_bad_spec() {
+        assert!(matches!(ChainSpec::from_json("{}"), Err(ChainSpecError::Parse(_))));
+        let easy = r#"{"network": "x", "genesis_timestamp": 0, "initial_bits": 553648127}"#;
+        assert_eq!(ChainSpec::from_json(easy), Err(ChainSpecError::InvalidBits(553_648_127)));
+
+        let unnamed = r#"{"network": "", "genesis_timestamp": 0, "initial_bits": 521142272}"#;
+        assert!(matches!(ChainSpec::from_json(unnamed), Err(ChainSpecError::Parse(_))));
+        let twice = r#"{"network": "x", "genesis_timestamp": 0, "initial_bits": 521142272,
+            "allocations": [{"address": "alice", "amount": 1}, {"address": "alice", "amount": 1}]}"#;
+        assert_eq!(
+            ChainSpec::from_json(twice),
+            Err(ChainSpecError::Parse("duplicate allocation for alice".to_string()))
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use crate::errors::EncodingError;
+
+/// Versão do formato binário; vai no primeiro byte de cada estrutura codificada.
+pub const ENCODING_VERSION: u8 = 1;
+
+/// Limite para campos com tamanho prefixado (evita alocar lixo vindo da rede).
+pub const MAX_FIELD_LEN: u32 = 4 * 1024 * 1024;
+
+/// Escreve inteiros em big-endian e bytes/strings com prefixo de tamanho (u32).
+#[derive(Debug, Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Encoder::default()
+    }
+
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.buf.push(value);
+        self
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn i64(&mut self, value: i64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.u32(value.len() as u32);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub fn string(&mut self, value: &str) -> &mut Self {
+        self.bytes(value.as_bytes())
+    }
+
+    /// Campo opcional: tag 0 (ausente) ou 1 seguido do valor.
+    pub fn option(&mut self, value: Option<&[u8]>) -> &mut Self {
+        match value {
+            None => self.u8(0),
+            Some(bytes) => self.u8(1).bytes(bytes),
+        }
+    }
+
+    pub fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+}
+
+/// Leitor estrito: qualquer desvio do formato canônico é erro.
+pub struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Decoder { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], EncodingError> {
+        let end = self.pos.checked_add(len).ok_or(EncodingError::UnexpectedEnd)?;
+        if end > self.data.len() {
+            return Err(EncodingError::UnexpectedEnd);
+        }
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, EncodingError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, EncodingError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, EncodingError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, EncodingError> {
+        Ok(i64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], EncodingError> {
+        let len = self.u32()?;
+        if len > MAX_FIELD_LEN {
+            return Err(EncodingError::LengthTooLarge(len));
+        }
+        self.take(len as usize)
+    }
+
+    pub fn string(&mut self) -> Result<String, EncodingError> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| EncodingError::InvalidUtf8)
+    }
+
+    pub fn option(&mut self) -> Result<Option<&'a [u8]>, EncodingError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.bytes()?)),
+            tag => Err(EncodingError::InvalidTag(tag)),
+        }
+    }
+
+    pub fn version(&mut self) -> Result<(), EncodingError> {
+        match self.u8()? {
+            ENCODING_VERSION => Ok(()),
+            other => Err(EncodingError::UnsupportedVersion(other)),
+        }
+    }
+
+    /// Tem que ter consumido tudo; bytes sobrando não são canônicos.
+    pub fn finish(self) -> Result<(), EncodingError> {
+        let remaining = self.data.len() - self.pos;
+        if remaining != 0 {
+            return Err(EncodingError::TrailingBytes(remaining));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_primitives() {
+        let bytes = Encoder::new()
+            .u8(ENCODING_VERSION)
+            .u32(7)
+            .u64(u64::MAX)
+            .i64(-5)
+            .string("olá")
+            .option(None)
+            .option(Some(&[1, 2, 3]))
+            .finish();
+
+        let mut decoder = Decoder::new(&bytes);
+        decoder.version().unwrap();
+        assert_eq!(decoder.u32().unwrap(), 7);
+        assert_eq!(decoder.u64().unwrap(), u64::MAX);
+        assert_eq!(decoder.i64().unwrap(), -5);
+        assert_eq!(decoder.string().unwrap(), "olá");
+        assert_eq!(decoder.option().unwrap(), None);
+        assert_eq!(decoder.option().unwrap(), Some(&[1u8, 2, 3][..]));
+        decoder.finish().unwrap();
+    }
+
+    #[test]
+    fn test_rejects_non_canonical_input() {
+        // tamanho maior que o que sobrou
+        let mut decoder = Decoder::new(&[0, 0, 0, 5, b'a']);
+        assert_eq!(decoder.string(), Err(EncodingError::UnexpectedEnd));
+
+        // tag de option diferente de 0/1
+        let mut decoder = Decoder::new(&[2]);
+        assert_eq!(decoder.option(), Err(EncodingError::InvalidTag(2)));
+
+        // versão desconhecida
+        let mut decoder = Decoder::new(&[99]);
+        assert_eq!(decoder.version(), Err(EncodingError::UnsupportedVersion(99)));
+
+        // bytes sobrando
+        let mut decoder = Decoder::new(&[1, 0]);
+        decoder.u8().unwrap();
+        assert_eq!(decoder.finish(), Err(EncodingError::TrailingBytes(1)));
+
+        // utf-8 inválido
+        let mut decoder = Decoder::new(&[0, 0, 0, 1, 0xff]);
+        assert_eq!(decoder.string(), Err(EncodingError::InvalidUtf8));
+
+        // tamanho absurdo
+        let mut decoder = Decoder::new(&[0xff, 0xff, 0xff, 0xff]);
+        assert!(matches!(decoder.bytes(), Err(EncodingError::LengthTooLarge(_))));
+    }
+}
@@ -1,11 +1,17 @@
 use std::fmt;
 use axum::{http::StatusCode, response::IntoResponse};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TransactionError {
     InvalidAmount,
     InvalidSignature(String),
     InvalidTx(String),
+    InsufficientFunds { address: String, balance: u64, amount: u64 },
+    InvalidCoinbase(String),
+    InvalidNonce { address: String, expected: u64, got: u64 },
+    Duplicate(String),
+    MempoolFull,
+    AddressMismatch { from_address: String, derived_address: String },
 }
 
 impl fmt::Display for TransactionError {
@@ -14,6 +20,29 @@ impl fmt::Display for TransactionError {
             TransactionError::InvalidAmount => write!(f, "Invalid transaction amount"),
             TransactionError::InvalidSignature(msg) => write!(f, "Invalid signature: {}", msg),
             TransactionError::InvalidTx(msg) => write!(f, "Invalid transaction: {}", msg),
+            TransactionError::InsufficientFunds { address, balance, amount } => write!(
+                f,
+                "Insufficient funds: {} has {} but tried to spend {}",
+                address, balance, amount
+            ),
+            TransactionError::InvalidCoinbase(msg) => write!(f, "Invalid coinbase: {}", msg),
+            TransactionError::InvalidNonce { address, expected, got } if got < expected => write!(
+                f,
+                "Nonce {} already used by {} (next is {})",
+                got, address, expected
+            ),
+            TransactionError::InvalidNonce { address, expected, got } => write!(
+                f,
+                "Nonce gap for {}: expected {} but got {}",
+                address, expected, got
+            ),
+            TransactionError::Duplicate(tx_hash) => write!(f, "Transaction {} is already in the mempool", tx_hash),
+            TransactionError::MempoolFull => write!(f, "Mempool is full and the fee rate is too low"),
+            TransactionError::AddressMismatch { from_address, derived_address } => write!(
+                f,
+                "from_address {} does not belong to the signing key (its address is {})",
+                from_address, derived_address
+            ),
         }
     }
 }
@@ -26,7 +55,195 @@ impl IntoResponse for TransactionError {
             TransactionError::InvalidAmount => (StatusCode::BAD_REQUEST, "Invalid amount".to_string()),
             TransactionError::InvalidSignature(msg) => (StatusCode::BAD_REQUEST, msg),
             TransactionError::InvalidTx(msg) => (StatusCode::BAD_REQUEST, msg),
+            err @ TransactionError::InsufficientFunds { .. } => (StatusCode::BAD_REQUEST, err.to_string()),
+            TransactionError::InvalidCoinbase(msg) => (StatusCode::BAD_REQUEST, msg),
+            err @ TransactionError::InvalidNonce { .. } => (StatusCode::BAD_REQUEST, err.to_string()),
+            err @ TransactionError::Duplicate(_) => (StatusCode::CONFLICT, err.to_string()),
+            err @ TransactionError::MempoolFull => (StatusCode::SERVICE_UNAVAILABLE, err.to_string()),
+            err @ TransactionError::AddressMismatch { .. } => (StatusCode::BAD_REQUEST, err.to_string()),
         };
         (status, error_message).into_response()
     }
-}
\ No newline at end of file
+}
+
+/// Motivos para rejeitar um bloco ao conectá-lo na chain.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockError {
+    NotNextBlock { expected: u64, got: u64 },
+    WrongPreviousHash,
+    UnexpectedBits { expected: u32, got: u32 },
+    InvalidHash,
+    InvalidMerkleRoot,
+    InsufficientProofOfWork,
+    TimestampTooOld { timestamp: i64, median_time_past: i64 },
+    TimestampTooFarInFuture { timestamp: i64, max_allowed: i64 },
+    TooLarge { size: usize, max: usize },
+    TooManyTransactions { count: usize, max: usize },
+    DuplicateTransaction(String),
+    DifferentGenesis,
+    CheckpointMismatch { height: u64, expected: String, got: String },
+    ReorgBelowCheckpoint { fork_height: u64, checkpoint_height: u64 },
+    BranchTooFarBehind { height: u64 },
+    Storage(String),
+    Transaction(TransactionError),
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::NotNextBlock { expected, got } => {
+                write!(f, "Block index {} is not the next height {}", got, expected)
+            }
+            BlockError::WrongPreviousHash => write!(f, "previous_hash does not match the chain tip"),
+            BlockError::UnexpectedBits { expected, got } => {
+                write!(f, "Block bits {:#x} differ from the required {:#x}", got, expected)
+            }
+            BlockError::InvalidHash => write!(f, "Block hash does not match its header"),
+            BlockError::InvalidMerkleRoot => write!(f, "merkle_root does not match the transactions"),
+            BlockError::InsufficientProofOfWork => write!(f, "Block hash is above its target"),
+            BlockError::TimestampTooOld { timestamp, median_time_past } => write!(
+                f,
+                "Block timestamp {} is not after the median time past {}",
+                timestamp, median_time_past
+            ),
+            BlockError::TimestampTooFarInFuture { timestamp, max_allowed } => write!(
+                f,
+                "Block timestamp {} is too far in the future (max {})",
+                timestamp, max_allowed
+            ),
+            BlockError::TooLarge { size, max } => write!(f, "Block has {} bytes of transactions (max {})", size, max),
+            BlockError::TooManyTransactions { count, max } => {
+                write!(f, "Block has {} transactions (max {})", count, max)
+            }
+            BlockError::DuplicateTransaction(tx_hash) => write!(f, "Transaction {} appears twice in the block", tx_hash),
+            BlockError::DifferentGenesis => write!(f, "Chain does not share our genesis block"),
+            BlockError::CheckpointMismatch { height, expected, got } => write!(
+                f,
+                "Block {} at checkpoint height {} should be {}",
+                got, height, expected
+            ),
+            BlockError::ReorgBelowCheckpoint { fork_height, checkpoint_height } => write!(
+                f,
+                "Reorg from height {} would undo the checkpoint at height {}",
+                fork_height, checkpoint_height
+            ),
+            BlockError::BranchTooFarBehind { height } => write!(
+                f,
+                "Side branch block at height {} is too far behind the active chain",
+                height
+            ),
+            BlockError::Storage(msg) => write!(f, "Could not store block: {}", msg),
+            BlockError::Transaction(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+impl IntoResponse for BlockError {
+    fn into_response(self) -> axum::response::Response {
+        let status = match self {
+            // bloco válido, mas a chain já andou: o minerador precisa de um template novo
+            BlockError::NotNextBlock { .. } | BlockError::WrongPreviousHash => StatusCode::CONFLICT,
+            BlockError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+impl From<TransactionError> for BlockError {
+    fn from(err: TransactionError) -> Self {
+        BlockError::Transaction(err)
+    }
+}
+
+/// Erros ao decodificar o formato binário canônico (ver `encoding`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodingError {
+    UnexpectedEnd,
+    UnsupportedVersion(u8),
+    InvalidTag(u8),
+    InvalidUtf8,
+    LengthTooLarge(u32),
+    TrailingBytes(usize),
+    NonCanonical(String),
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::UnexpectedEnd => write!(f, "Unexpected end of input"),
+            EncodingError::UnsupportedVersion(v) => write!(f, "Unsupported encoding version {}", v),
+            EncodingError::InvalidTag(tag) => write!(f, "Invalid option tag {}", tag),
+            EncodingError::InvalidUtf8 => write!(f, "Invalid UTF-8 string"),
+            EncodingError::LengthTooLarge(len) => write!(f, "Length prefix too large: {}", len),
+            EncodingError::TrailingBytes(n) => write!(f, "{} trailing bytes after value", n),
+            EncodingError::NonCanonical(msg) => write!(f, "Non-canonical encoding: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+impl From<EncodingError> for TransactionError {
+    fn from(err: EncodingError) -> Self {
+        TransactionError::InvalidTx(err.to_string())
+    }
+}
+
+/// Erros ao carregar a chain spec (ver `chain_spec`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainSpecError {
+    Io(String),
+    Parse(String),
+    InvalidBits(u32),
+    InvalidAllocations(String),
+    GenesisMismatch { expected: String, got: String },
+}
+
+impl fmt::Display for ChainSpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainSpecError::Io(msg) => write!(f, "Could not read chain spec: {}", msg),
+            ChainSpecError::Parse(msg) => write!(f, "Invalid chain spec: {}", msg),
+            ChainSpecError::InvalidBits(bits) => write!(f, "Invalid initial bits {:#010x}", bits),
+            ChainSpecError::InvalidAllocations(msg) => write!(f, "Invalid genesis allocations: {}", msg),
+            ChainSpecError::GenesisMismatch { expected, got } => write!(
+                f,
+                "Genesis hash mismatch: spec expects {} but built {}",
+                expected, got
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChainSpecError {}
+
+/// Erros do armazenamento em disco (ver `block_store`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageError {
+    Io(String),
+    Corrupt { segment: u32, offset: u64, reason: String },
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Io(msg) => write!(f, "Storage I/O error: {}", msg),
+            StorageError::Corrupt { segment, offset, reason } => write!(
+                f,
+                "Corrupt record in segment {} at offset {}: {}",
+                segment, offset, reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<std::io::Error> for StorageError {
+    fn from(err: std::io::Error) -> Self {
+        StorageError::Io(err.to_string())
+    }
+}
@@ -2,22 +2,29 @@ mod blockchain;
 mod wallet;
 mod routes;
 mod errors;
+mod encoding;
+mod chain_spec;
 
-use std::{net::SocketAddr, sync::{Arc, Mutex}};
+use std::{net::SocketAddr, path::PathBuf, sync::{Arc, Mutex}};
 use axum::{
     routing::{get, post},
     Router,
 };
 use clap::Parser;
-use tokio;
 use crate::wallet::wallet::Wallet; 
-use std::collections::HashMap;
+use crate::chain_spec::ChainSpec;
+use std::collections::{HashMap, HashSet};
 
+use crate::blockchain::blockchain::Blockchain;
+use crate::blockchain::mempool::{Mempool, MempoolConfig, DEFAULT_MAX_BYTES, DEFAULT_MAX_COUNT, DEFAULT_TTL};
+use crate::blockchain::miner::{Miner, MiningJob};
 use crate::blockchain::node::Node;
 use crate::routes::{
-    chain_routes::{get_chain_handler, mine_handler, get_mempool_handler, sync_chain_handler},
+    block_routes::{get_block_handler, get_blocks_at_height_handler, announce_block_handler},
+    chain_routes::{auto_mine, get_chain_handler, mine_handler, get_mining_job_handler, cancel_mining_job_handler, get_mempool_handler, sync_chain_handler, get_tips_handler, get_balance_handler},
+    mining_routes::{get_block_template_handler, submit_block_handler},
     peer_routes::{get_peers_handler, add_peer_handler},
-    transaction_routes::{receive_transaction_handler, create_transaction_handler},
+    transaction_routes::{receive_transaction_handler, receive_raw_transaction_handler, create_transaction_handler, get_transaction_proof_handler, verify_transaction_proof_handler},
     wallet_routes::create_wallet_handler,
 };
 
@@ -29,13 +36,68 @@ struct Args {
 
     #[clap(long, default_value="")]
     peers: String,
+
+    /// host:port pelo qual os peers conhecem este nó (padrão: 127.0.0.1:<port>)
+    #[clap(long)]
+    public_address: Option<String>,
+
+    /// Minera continuamente em background
+    #[clap(long, requires="miner_address")]
+    mine: bool,
+
+    /// Endereço que recebe a coinbase no modo --mine
+    #[clap(long)]
+    miner_address: Option<String>,
+
+    /// Threads do minerador (padrão: todos os núcleos)
+    #[clap(long)]
+    mine_threads: Option<usize>,
+
+    /// Máximo de transações pendentes no mempool
+    #[clap(long, default_value_t=DEFAULT_MAX_COUNT)]
+    mempool_max_txs: usize,
+
+    /// Máximo de bytes de transações pendentes no mempool
+    #[clap(long, default_value_t=DEFAULT_MAX_BYTES)]
+    mempool_max_bytes: usize,
+
+    /// Segundos até uma transação pendente expirar
+    #[clap(long, default_value_t=DEFAULT_TTL)]
+    mempool_ttl: i64,
+
+    /// Arquivo JSON com a spec da rede (gênese, dificuldade inicial, alocações)
+    #[clap(long)]
+    chain_spec: Option<PathBuf>,
+
+    /// Diretório onde blocos e mempool são gravados; sem ele tudo fica só em memória
+    #[clap(long)]
+    data_dir: Option<PathBuf>,
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub node: Arc<Mutex<Node>>,
     pub peers: Arc<Mutex<Vec<String>>>,
+    // vai no `from` dos anúncios de bloco, para o peer saber a quem pedir os pais
+    pub address: String,
     pub wallets: Arc<Mutex<HashMap<String, Wallet>>>,
+    // jobs de mineração por id; os em andamento são cancelados quando chega um bloco concorrente
+    pub mining_jobs: Arc<Mutex<HashMap<String, MiningJob>>>,
+    // peers para os quais já há uma busca de ancestrais em andamento
+    pub parent_fetches: Arc<Mutex<HashSet<String>>>,
+}
+
+impl AppState {
+    pub fn new(node: Node, peers: Vec<String>, address: String) -> Self {
+        AppState {
+            node: Arc::new(Mutex::new(node)),
+            peers: Arc::new(Mutex::new(peers)),
+            address,
+            wallets: Arc::new(Mutex::new(HashMap::new())),
+            mining_jobs: Arc::new(Mutex::new(HashMap::new())),
+            parent_fetches: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
 }
 
 #[tokio::main]
@@ -53,21 +115,65 @@ async fn main() {
             .collect()
     };
 
-    let node = Node::new(1);
-    let state = AppState {
-        node: Arc::new(Mutex::new(node)),
-        peers: Arc::new(Mutex::new(peers_list)),
-        wallets: Arc::new(Mutex::new(HashMap::new())),
+    let spec = match &args.chain_spec {
+        Some(path) => ChainSpec::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        None => ChainSpec::default(),
     };
+    // confere o genesis_hash da spec antes de subir
+    let mut blockchain = Blockchain::from_spec(&spec).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    println!("Rede {}, gênese {}", spec.network, blockchain.blocks[0].hash);
+    blockchain.mempool = Mempool::new(MempoolConfig {
+        max_count: args.mempool_max_txs,
+        max_bytes: args.mempool_max_bytes,
+        ttl: args.mempool_ttl,
+    });
+    // recarrega e revalida o que foi gravado antes de aceitar blocos novos
+    if let Some(data_dir) = &args.data_dir {
+        if let Err(e) = blockchain.attach_store(data_dir) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let mut node = Node::new(1);
+    node.blockchain = blockchain;
+    let address = args.public_address.clone().unwrap_or_else(|| format!("127.0.0.1:{}", port));
+    let state = AppState::new(node, peers_list, address);
+
+    if args.mine {
+        let miner = match args.mine_threads {
+            Some(threads) => Miner::new(threads),
+            None => Miner::with_available_parallelism(),
+        };
+        let reward_address = args.miner_address.clone().expect("--mine exige --miner-address");
+        tokio::spawn(auto_mine(state.clone(), reward_address, miner));
+    }
 
     let app = Router::new()
         .route("/chain", get(get_chain_handler))
         .route("/mine", post(mine_handler))
+        .route("/mine/:job_id", get(get_mining_job_handler).delete(cancel_mining_job_handler))
+        .route("/mining/template", get(get_block_template_handler))
+        .route("/mining/submit", post(submit_block_handler))
+        .route("/block/announce", post(announce_block_handler))
+        .route("/block/:hash", get(get_block_handler))
+        .route("/block/height/:height", get(get_blocks_at_height_handler))
         .route("/transaction", post(receive_transaction_handler))
+        .route("/transaction/raw", post(receive_raw_transaction_handler))
         .route("/transaction/create", post(create_transaction_handler))
+        .route("/tx/:hash/proof", get(get_transaction_proof_handler))
+        .route("/tx/proof/verify", post(verify_transaction_proof_handler))
         .route("/peers", get(get_peers_handler).post(add_peer_handler))
         .route("/mempool", get(get_mempool_handler))
         .route("/chain/sync", post(sync_chain_handler))
+        .route("/chain/tips", get(get_tips_handler))
+        .route("/balance/:address", get(get_balance_handler))
         .route("/wallet/create", post(create_wallet_handler))
         .with_state(state);
 
@@ -0,0 +1,160 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    http::StatusCode,
+    Json,
+};
+use crate::AppState;
+use crate::blockchain::block::Block;
+use crate::blockchain::blockchain::BlockStatus;
+use crate::blockchain::orphan_pool::DEFAULT_MAX_ORPHANS;
+use crate::errors::BlockError;
+use crate::routes::chain_routes::cancel_stale_jobs;
+use serde::Deserialize;
+use serde_json::json;
+
+/// GET /block/:hash - bloco conhecido, da chain ativa ou de um ramo lateral
+pub async fn get_block_handler(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> impl IntoResponse {
+    let node_guard = state.node.lock().unwrap();
+    match node_guard.blockchain.get_block(&hash) {
+        Some(block) => (StatusCode::OK, Json(json!(block))),
+        None => (StatusCode::NOT_FOUND, Json(json!({"message": "Block not found"}))),
+    }
+}
+
+/// GET /block/height/:height - bloco da chain ativa nessa altura e os hashes de
+/// todos os gravados nela (mais de um quando houve fork; só com --data-dir)
+pub async fn get_blocks_at_height_handler(
+    State(state): State<AppState>,
+    Path(height): Path<u64>,
+) -> impl IntoResponse {
+    let node_guard = state.node.lock().unwrap();
+    let blockchain = &node_guard.blockchain;
+    let active = blockchain.blocks.get(height as usize);
+    let stored = blockchain.stored_hashes_at(height);
+    if active.is_none() && stored.is_empty() {
+        return (StatusCode::NOT_FOUND, Json(json!({"message": "No block at this height"})));
+    }
+    (StatusCode::OK, Json(json!({
+        "height": height,
+        "active": active,
+        "stored": stored
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct AnnounceRequest {
+    pub block: Block,
+    /// host:port de quem anunciou; os pais que faltarem só são pedidos se ele
+    /// estiver na nossa lista de peers
+    pub from: String,
+}
+
+/// POST /block/announce - recebe um bloco novo de um peer. Se o pai for
+/// desconhecido o bloco fica no pool de órfãos e os ancestrais são pedidos
+/// ao peer em background, uma busca por vez para cada peer.
+pub async fn announce_block_handler(
+    State(state): State<AppState>,
+    Json(request): Json<AnnounceRequest>,
+) -> Result<impl IntoResponse, BlockError> {
+    let hash = request.block.hash.clone();
+    let status = receive_block(&state, request.block)?;
+
+    if let BlockStatus::Orphaned { missing_parent } = &status {
+        if claim_parent_fetch(&state, &request.from) {
+            println!("Bloco {} é órfão, pedindo {} para {}", hash, missing_parent, request.from);
+            let (state, peer, missing) = (state.clone(), request.from, missing_parent.clone());
+            tokio::spawn(async move {
+                fetch_missing_parents(&state, &peer, missing).await;
+                state.parent_fetches.lock().unwrap().remove(&peer);
+            });
+        } else {
+            println!("Bloco {} é órfão; ancestrais não pedidos a {}", hash, request.from);
+        }
+    }
+    Ok((StatusCode::ACCEPTED, Json(json!({"hash": hash, "result": status}))))
+}
+
+// só pedimos blocos a peers que nós mesmos configuramos (senão qualquer um faria
+// o nó disparar requisições para um endereço à escolha dele), e no máximo uma
+// busca por peer de cada vez
+fn claim_parent_fetch(state: &AppState, peer: &str) -> bool {
+    if !state.peers.lock().unwrap().iter().any(|known| known == peer) {
+        return false;
+    }
+    state.parent_fetches.lock().unwrap().insert(peer.to_string())
+}
+
+// passa o bloco pela chain e cancela jobs de mineração que ficaram para trás
+fn receive_block(state: &AppState, block: Block) -> Result<BlockStatus, BlockError> {
+    let mut node_guard = state.node.lock().unwrap();
+    let status = node_guard.blockchain.add_block_from_network(block)?;
+    if matches!(status, BlockStatus::Connected | BlockStatus::Reorganized) {
+        let tip_hash = node_guard.blockchain.blocks.last().unwrap().hash.clone();
+        cancel_stale_jobs(state, &tip_hash);
+    }
+    Ok(status)
+}
+
+// pede ao peer um pai por vez até a ancestralidade encostar na nossa chain;
+// no máximo o tamanho do pool, já que os órfãos mais antigos seriam despejados
+async fn fetch_missing_parents(state: &AppState, peer: &str, mut missing: String) {
+    let client = reqwest::Client::new();
+    for _ in 0..DEFAULT_MAX_ORPHANS {
+        let url = format!("http://{}/block/{}", peer, missing);
+        let response = match client.get(&url).send().await {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                eprintln!("Peer {} não devolveu o bloco {}: {}", peer, missing, response.status());
+                return;
+            }
+            Err(e) => {
+                eprintln!("Erro ao pedir bloco {} para {}: {:?}", missing, peer, e);
+                return;
+            }
+        };
+        let block: Block = match response.json().await {
+            Ok(block) => block,
+            Err(e) => {
+                eprintln!("Resposta inválida de {} para o bloco {}: {:?}", peer, missing, e);
+                return;
+            }
+        };
+        if block.hash != missing {
+            eprintln!("Peer {} mandou {} no lugar de {}", peer, block.hash, missing);
+            return;
+        }
+
+        match receive_block(state, block) {
+            Ok(BlockStatus::Orphaned { missing_parent }) => missing = missing_parent,
+            Ok(status) => {
+                println!("Ancestrais recebidos de {}: {:?}", peer, status);
+                return;
+            }
+            Err(e) => {
+                eprintln!("Bloco {} de {} rejeitado: {}", missing, peer, e);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::node::Node;
+
+    #[test]
+    fn test_parents_are_fetched_only_from_known_peers_one_at_a_time() {
+        let state = AppState::new(Node::new_random_id(), vec!["127.0.0.1:3001".to_string()], "127.0.0.1:3000".to_string());
+        assert!(!claim_parent_fetch(&state, "169.254.169.254:80"));
+
+        assert!(claim_parent_fetch(&state, "127.0.0.1:3001"));
+        assert!(!claim_parent_fetch(&state, "127.0.0.1:3001"), "já existe uma busca em andamento");
+        state.parent_fetches.lock().unwrap().remove("127.0.0.1:3001");
+        assert!(claim_parent_fetch(&state, "127.0.0.1:3001"));
+    }
+}
@@ -1,11 +1,23 @@
 use axum::{
-    extract::{State},
+    extract::{Path, State},
     response::IntoResponse,
     http::StatusCode,
     Json,
 };
 use crate::AppState;
+use crate::blockchain::block::Block;
+use crate::blockchain::miner::{
+    prune_finished_jobs, Miner, MiningControl, MiningJob, MiningStatus, FINISHED_JOB_TTL, MAX_FINISHED_JOBS,
+};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
 use serde_json::json;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+// espera do --mine quando outro job já está minerando o topo atual
+const BUSY_RETRY_DELAY: Duration = Duration::from_secs(1);
 
 pub async fn get_chain_handler(State(state): State<AppState>) -> impl IntoResponse {
     let node_guard = state.node.lock().unwrap();
@@ -13,57 +25,365 @@ pub async fn get_chain_handler(State(state): State<AppState>) -> impl IntoRespon
     (StatusCode::OK, Json(blocks))
 }
 
-pub async fn mine_handler(State(state): State<AppState>) -> impl IntoResponse {
+#[derive(Deserialize)]
+pub struct MineRequest {
+    pub reward_address: String,
+    // número de threads do minerador; padrão: todos os núcleos
+    pub threads: Option<usize>,
+}
+
+/// Cria um job de mineração em background e devolve o id na hora. Se já houver
+/// um job minerando em cima do topo atual, responde 409 com o id dele.
+pub async fn mine_handler(
+    State(state): State<AppState>,
+    Json(request): Json<MineRequest>,
+) -> impl IntoResponse {
+    let miner = match request.threads {
+        Some(threads) => Miner::new(threads),
+        None => Miner::with_available_parallelism(),
+    };
+    let (job_id, height, _) = match start_mining_job(&state, &request.reward_address, miner) {
+        Ok(started) => started,
+        Err(running) => {
+            return (StatusCode::CONFLICT, Json(json!({
+                "message": "A mining job is already running on this tip",
+                "job_id": running
+            })));
+        }
+    };
+
+    let response = json!({
+        "message": "Mining job started",
+        "job_id": job_id,
+        "height": height,
+        "reward_address": request.reward_address
+    });
+    (StatusCode::ACCEPTED, Json(response))
+}
+
+/// Monta o template, registra o job e minera numa thread bloqueante, sem segurar
+/// o lock do nó. O handle devolve o status final do job. Só um job por topo: se
+/// já houver um rodando em cima do mesmo pai, devolve o id dele como erro.
+pub fn start_mining_job(
+    state: &AppState,
+    reward_address: &str,
+    miner: Miner,
+) -> Result<(String, u64, JoinHandle<MiningStatus>), String> {
+    let job_id = Uuid::new_v4().to_string();
+    // monta o template e registra o job com o lock do nó, para que um topo novo
+    // não passe entre os dois sem cancelar o job; solta antes de minerar
+    let (template, control) = {
+        let node_guard = state.node.lock().unwrap();
+        let template = node_guard.blockchain.create_block_template(reward_address);
+        let mut jobs = state.mining_jobs.lock().unwrap();
+        // cada job ocupa todos os núcleos que pediu; dois no mesmo topo só disputam CPU
+        let running = jobs
+            .iter()
+            .find(|(_, job)| job.is_running() && job.control.previous_hash == template.previous_hash);
+        if let Some((running_id, _)) = running {
+            return Err(running_id.clone());
+        }
+        let control = Arc::new(MiningControl::new(template.index, template.previous_hash.clone()));
+        let job = MiningJob::new(Arc::clone(&control), reward_address.to_string(), miner.threads);
+        // jobs encerrados ficam consultáveis por um tempo, depois saem
+        prune_finished_jobs(&mut jobs, Instant::now(), FINISHED_JOB_TTL, MAX_FINISHED_JOBS);
+        jobs.insert(job_id.clone(), job);
+        (template, control)
+    };
+    let height = template.index;
+
+    let job_state = state.clone();
+    let id = job_id.clone();
+    let handle = tokio::spawn(async move {
+        let mined = tokio::task::spawn_blocking(move || miner.mine(&template, &control)).await;
+        let status = match mined {
+            Ok(Ok(result)) => {
+                println!(
+                    "Job {} terminou: {} tentativas em {:?} ({:.0} H/s)",
+                    id, result.attempts, result.elapsed, result.hashrate()
+                );
+                connect_mined_block(&job_state, result.block)
+            }
+            // sem isso o job ficaria Running para sempre
+            Ok(Err(e)) => MiningStatus::Failed { reason: e.to_string() },
+            Err(e) => MiningStatus::Failed { reason: e.to_string() },
+        };
+        if let Some(job) = job_state.mining_jobs.lock().unwrap().get_mut(&id) {
+            job.finish(status.clone());
+        }
+        status
+    });
+    Ok((job_id, height, handle))
+}
+
+/// Modo `--mine`: minera sem parar para `reward_address`. Quando chega uma tip nova
+/// o job atual é cancelado e o loop recomeça com um template atualizado.
+pub async fn auto_mine(state: AppState, reward_address: String, miner: Miner) {
+    println!("Mineração contínua ligada para {} com {} threads", reward_address, miner.threads);
+    loop {
+        let (job_id, height, handle) = match start_mining_job(&state, &reward_address, miner.clone()) {
+            Ok(started) => started,
+            Err(running) => {
+                // um job pedido via POST /mine já cobre este topo; tenta de novo depois
+                println!("Auto-mining: job {} já minera este topo, aguardando", running);
+                let _ = tokio::task::spawn_blocking(|| std::thread::sleep(BUSY_RETRY_DELAY)).await;
+                continue;
+            }
+        };
+        let joined = handle.await;
+        // os jobs do loop não ficam acumulando no registro
+        if let Some(job) = state.mining_jobs.lock().unwrap().remove(&job_id) {
+            // se a task morreu no meio, as threads de mineração não podem ficar soltas
+            job.control.cancel();
+        }
+        let status = match joined {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("Auto-mining: job {} da altura {} morreu ({}), recomeçando", job_id, height, e);
+                continue;
+            }
+        };
+        match status {
+            MiningStatus::Found { hash, .. } => println!("Auto-mining: bloco {} minerado ({})", height, hash),
+            MiningStatus::Cancelled => println!("Auto-mining: nova tip recebida, reiniciando na altura {}", height),
+            MiningStatus::Stale { reason } => println!("Auto-mining: bloco {} descartado: {}", height, reason),
+            MiningStatus::Failed { reason } => {
+                eprintln!("Auto-mining: minerador falhou ({}), parando", reason);
+                return;
+            }
+            MiningStatus::Running => {}
+        }
+    }
+}
+
+/// Cancela os jobs cujo template não estende mais o topo `tip_hash`. Olhar só a
+/// altura não basta: um reorg para um ramo mais pesado pode terminar na mesma
+/// altura ou abaixo dela.
+pub fn cancel_stale_jobs(state: &AppState, tip_hash: &str) {
+    for job in state.mining_jobs.lock().unwrap().values() {
+        if job.is_running() && job.control.previous_hash != tip_hash {
+            job.control.cancel();
+        }
+    }
+}
+
+// conecta o bloco achado pelo job e propaga; devolve o status final do job
+fn connect_mined_block(state: &AppState, block: Option<Block>) -> MiningStatus {
+    let Some(block) = block else {
+        return MiningStatus::Cancelled;
+    };
+
+    let (index, hash) = (block.index, block.hash.clone());
     let mut node_guard = state.node.lock().unwrap();
-    node_guard.blockchain.add_block();
-    let new_block = node_guard.blockchain.blocks.last().unwrap().clone();
-    let idx = new_block.index;
-    let peers = state.peers.lock().unwrap().clone();
+    if let Err(e) = node_guard.blockchain.connect_block(block) {
+        // a chain mudou enquanto minerávamos
+        return MiningStatus::Stale { reason: e.to_string() };
+    }
+    let block = node_guard.blockchain.blocks.last().unwrap().clone();
     drop(node_guard);
 
-    // Propagar o bloco em background
+    cancel_stale_jobs(state, &hash);
+    announce_block(state, block);
+    MiningStatus::Found { index, hash }
+}
+
+pub async fn get_mining_job_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    let jobs = state.mining_jobs.lock().unwrap();
+    match jobs.get(&job_id) {
+        Some(job) => {
+            let elapsed = job.elapsed();
+            let attempts = job.control.attempts();
+            let secs = elapsed.as_secs_f64();
+            let hashrate = if secs > 0.0 { attempts as f64 / secs } else { 0.0 };
+            (StatusCode::OK, Json(json!({
+                "job_id": job_id,
+                "height": job.control.height,
+                "reward_address": job.reward_address,
+                "threads": job.threads,
+                "status": job.status,
+                "attempts": attempts,
+                "elapsed_ms": elapsed.as_millis() as u64,
+                "hashrate": hashrate
+            })))
+        }
+        None => (StatusCode::NOT_FOUND, Json(json!({"message": "Mining job not found"}))),
+    }
+}
+
+/// Aborta o job; o worker percebe o cancelamento no próximo lote de nonces.
+pub async fn cancel_mining_job_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    let jobs = state.mining_jobs.lock().unwrap();
+    match jobs.get(&job_id) {
+        Some(job) if job.is_running() => {
+            job.control.cancel();
+            (StatusCode::OK, Json(json!({"message": "Mining job cancelled", "job_id": job_id})))
+        }
+        Some(job) => (StatusCode::CONFLICT, Json(json!({
+            "message": "Mining job already finished",
+            "status": job.status
+        }))),
+        None => (StatusCode::NOT_FOUND, Json(json!({"message": "Mining job not found"}))),
+    }
+}
+
+/// Anuncia o bloco novo no /block/announce de cada peer, em background. Se o
+/// peer não tiver o pai ele pede os ancestrais de volta para `state.address`.
+/// Peer que recusar o anúncio (um nó antigo, sem a rota) recebe a chain inteira
+/// no /chain/sync.
+pub fn announce_block(state: &AppState, block: Block) {
+    let peers = state.peers.lock().unwrap().clone();
+    let announcement = announcement(state, block);
+    let state = state.clone();
     let client = reqwest::Client::new();
     tokio::spawn(async move {
         for peer in peers {
-            let url = format!("http://{}/chain/sync", peer);
-            let result = client
-                .post(&url)
-                .json(&new_block)
-                .send()
-                .await;
-            if let Err(e) = result {
-                eprintln!("Erro ao propagar bloco para {}: {:?}", peer, e);
+            let url = format!("http://{}/block/announce", peer);
+            match client.post(&url).json(&announcement).send().await {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => {
+                    eprintln!("Peer {} recusou o anúncio ({}), enviando a chain", peer, response.status());
+                    let blocks = state.node.lock().unwrap().blockchain.blocks.clone();
+                    sync_chain_to(&client, &peer, &blocks).await;
+                }
+                Err(e) => eprintln!("Erro ao anunciar bloco para {}: {:?}", peer, e),
             }
         }
     });
+}
 
-    let response = json!({
-        "message": "Mined new block",
-        "index": idx
-    });
-    (StatusCode::OK, Json(response))
+// corpo do POST /block/announce (AnnounceRequest do lado de quem recebe)
+fn announcement(state: &AppState, block: Block) -> serde_json::Value {
+    json!({ "block": block, "from": state.address })
+}
+
+// fallback do anúncio: a chain inteira no /chain/sync do peer
+async fn sync_chain_to(client: &reqwest::Client, peer: &str, blocks: &[Block]) {
+    let url = format!("http://{}/chain/sync", peer);
+    if let Err(e) = client.post(&url).json(blocks).send().await {
+        eprintln!("Erro ao propagar chain para {}: {:?}", peer, e);
+    }
 }
 
 pub async fn get_mempool_handler(State(state): State<AppState>) -> impl IntoResponse {
     let node_guard = state.node.lock().unwrap();
-    let pending = node_guard.blockchain.pending_transactions.clone();
+    let pending = node_guard.blockchain.pending_by_fee_rate();
+    let mempool = &node_guard.blockchain.mempool;
     let mempool_obj = json!({
-        "pending_transactions": pending
+        "pending_transactions": pending,
+        "count": mempool.len(),
+        "bytes": mempool.bytes(),
+        "config": mempool.config()
     });
     (StatusCode::OK, Json(mempool_obj))
 }
 
 pub async fn sync_chain_handler(
     State(state): State<AppState>,
-    Json(new_chain): Json<Vec<crate::blockchain::block::Block>>,
+    Json(new_chain): Json<Vec<Block>>,
 ) -> impl IntoResponse {
     let mut node_guard = state.node.lock().unwrap();
-    let current_length = node_guard.blockchain.blocks.len();
-    if new_chain.len() > current_length && node_guard.blockchain.is_valid() {
-        println!("Recebida blockchain maior, sincronizando...");
-        node_guard.blockchain.blocks = new_chain;
+    // replace_chain_if_heavier faz o reorg: valida os blocos novos a partir do fork e devolve ao mempool o que saiu
+    if node_guard.blockchain.replace_chain_if_heavier(&new_chain) {
+        println!("Recebida blockchain com mais trabalho, sincronizando...");
+        // quem estava minerando em cima do topo antigo está perdendo tempo
+        let tip_hash = node_guard.blockchain.blocks.last().unwrap().hash.clone();
+        cancel_stale_jobs(&state, &tip_hash);
         (StatusCode::OK, Json(json!({"message": "Blockchain synchronized"})))
     } else {
-        (StatusCode::BAD_REQUEST, Json(json!({"message": "Invalid or shorter chain"})))
+        (StatusCode::BAD_REQUEST, Json(json!({"message": "Invalid chain or less cumulative work"})))
     }
-}
\ No newline at end of file
+}
+
+/// GET /chain/tips - pontas de todos os ramos conhecidos; a ativa é a da chain atual
+pub async fn get_tips_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let node_guard = state.node.lock().unwrap();
+    let active_tip = node_guard.blockchain.blocks.last().map(|b| b.hash.clone());
+    let tips: Vec<_> = node_guard
+        .blockchain
+        .tips()
+        .into_iter()
+        .map(|tip| json!({
+            "hash": tip.hash,
+            "height": tip.height,
+            "chain_work": tip.chain_work.to_hex(),
+            "active": Some(&tip.hash) == active_tip.as_ref()
+        }))
+        .collect();
+    (StatusCode::OK, Json(json!({ "tips": tips })))
+}
+
+pub async fn get_balance_handler(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> impl IntoResponse {
+    let node_guard = state.node.lock().unwrap();
+    let balance = node_guard.blockchain.balance_of(&address);
+    // clientes que assinam por conta própria (/transaction/raw) usam este nonce
+    let next_nonce = node_guard.blockchain.next_nonce(&address);
+    (StatusCode::OK, Json(json!({
+        "address": address,
+        "balance": balance,
+        "next_nonce": next_nonce
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::node::Node;
+    use crate::routes::block_routes::AnnounceRequest;
+
+    #[test]
+    fn test_announcement_names_this_node() {
+        let state = AppState::new(Node::new_random_id(), vec![], "10.0.0.7:3000".to_string());
+        let block = state.node.lock().unwrap().blockchain.blocks[0].clone();
+        let request: AnnounceRequest = serde_json::from_value(announcement(&state, block.clone())).unwrap();
+        assert_eq!(request.block, block);
+        assert_eq!(request.from, "10.0.0.7:3000");
+    }
+
+    #[tokio::test]
+    async fn test_one_running_job_per_tip() {
+        let state = AppState::new(Node::new_random_id(), vec![], "127.0.0.1:3000".to_string());
+        let tip = state.node.lock().unwrap().blockchain.blocks[0].hash.clone();
+        let busy = Arc::new(MiningControl::new(1, tip.clone()));
+        let job = MiningJob::new(Arc::clone(&busy), "miner".to_string(), 1);
+        state.mining_jobs.lock().unwrap().insert("busy".to_string(), job);
+
+        assert_eq!(start_mining_job(&state, "other", Miner::new(1)).err(), Some("busy".to_string()));
+        assert_eq!(state.mining_jobs.lock().unwrap().len(), 1);
+
+        // o job do topo acabou: o próximo pode começar
+        state.mining_jobs.lock().unwrap().get_mut("busy").unwrap().finish(MiningStatus::Cancelled);
+        let (job_id, _, handle) = start_mining_job(&state, "other", Miner::new(1)).unwrap();
+        state.mining_jobs.lock().unwrap()[&job_id].control.cancel();
+        // na dificuldade inicial o bloco pode sair antes do cancelamento
+        assert!(matches!(handle.await.unwrap(), MiningStatus::Cancelled | MiningStatus::Found { .. }));
+    }
+
+    #[test]
+    fn test_jobs_on_a_replaced_parent_are_cancelled() {
+        let state = AppState::new(Node::new_random_id(), vec![], "127.0.0.1:3000".to_string());
+        let tip = state.node.lock().unwrap().blockchain.blocks[0].hash.clone();
+
+        // mesma altura, pais diferentes: só a altura não distingue os dois
+        let on_tip = Arc::new(MiningControl::new(1, tip.clone()));
+        let on_dead_parent = Arc::new(MiningControl::new(1, "ramo-abandonado".to_string()));
+        {
+            let mut jobs = state.mining_jobs.lock().unwrap();
+            for (id, control) in [("a", &on_tip), ("b", &on_dead_parent)] {
+                jobs.insert(id.to_string(), MiningJob::new(Arc::clone(control), "miner".to_string(), 1));
+            }
+        }
+
+        cancel_stale_jobs(&state, &tip);
+        assert!(!on_tip.is_cancelled());
+        assert!(on_dead_parent.is_cancelled());
+    }
+}
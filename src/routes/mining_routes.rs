@@ -0,0 +1,122 @@
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    http::StatusCode,
+    Json,
+};
+use crate::AppState;
+use crate::blockchain::block::Block;
+use crate::blockchain::pow::target_from_bits;
+use crate::errors::BlockError;
+use crate::routes::chain_routes::{announce_block, cancel_stale_jobs};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+pub struct TemplateQuery {
+    pub reward_address: String,
+}
+
+/// getblocktemplate: tudo que um minerador externo precisa para procurar o nonce.
+/// O hash é sha256(encode_header(index, timestamp, merkle_root, previous_hash, bits, nonce)).
+pub async fn get_block_template_handler(
+    State(state): State<AppState>,
+    Query(query): Query<TemplateQuery>,
+) -> impl IntoResponse {
+    let node_guard = state.node.lock().unwrap();
+    let template = node_guard.blockchain.create_block_template(&query.reward_address);
+    drop(node_guard);
+
+    let target = target_from_bits(template.bits).map(|t| t.to_hex());
+    let tx_hashes: Vec<String> = template.transactions.iter().map(|tx| tx.tx_hash()).collect();
+    (StatusCode::OK, Json(json!({
+        "index": template.index,
+        "previous_hash": template.previous_hash,
+        "timestamp": template.timestamp,
+        "bits": template.bits,
+        "target": target,
+        "merkle_root": template.merkle_root,
+        "tx_hashes": tx_hashes,
+        "transactions": template.transactions
+    })))
+}
+
+/// submitblock: recebe um bloco resolvido, valida e conecta no topo da chain.
+pub async fn submit_block_handler(
+    State(state): State<AppState>,
+    Json(block): Json<Block>,
+) -> Result<impl IntoResponse, BlockError> {
+    let (index, hash) = (block.index, block.hash.clone());
+    submit_block(&state, block)?;
+
+    Ok(Json(json!({
+        "message": "Block accepted",
+        "index": index,
+        "hash": hash
+    })))
+}
+
+// connect_block já roda a validação completa (hash, PoW, merkle, bits, ledger);
+// aceito o bloco, cancela a mineração local que ficou para trás e anuncia
+fn submit_block(state: &AppState, block: Block) -> Result<(), BlockError> {
+    let (index, hash) = (block.index, block.hash.clone());
+    state.node.lock().unwrap().blockchain.connect_block(block.clone())?;
+
+    println!("Bloco {} recebido de minerador externo: {}", index, hash);
+    cancel_stale_jobs(state, &hash);
+    announce_block(state, block);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::block::calculate_hash;
+    use crate::blockchain::blockchain::BLOCK_SUBSIDY;
+    use crate::blockchain::node::Node;
+    use crate::blockchain::pow::hash_meets_target;
+
+    // o que um minerador externo faz com o template: só varia o nonce
+    fn solve(mut block: Block) -> Block {
+        loop {
+            block.hash = calculate_hash(
+                block.index, block.timestamp, &block.merkle_root, &block.previous_hash, block.bits, block.nonce,
+            );
+            if hash_meets_target(&block.hash, block.bits) {
+                return block;
+            }
+            block.nonce += 1;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_solved_template_is_accepted_once() {
+        let state = AppState::new(Node::new_random_id(), vec![], "127.0.0.1:3000".to_string());
+        let (template, competing) = {
+            let node_guard = state.node.lock().unwrap();
+            let blockchain = &node_guard.blockchain;
+            (blockchain.create_block_template("miner"), blockchain.create_block_template("other"))
+        };
+
+        let block = solve(template);
+        submit_block(&state, block.clone()).expect("bloco resolvido deve conectar");
+        {
+            let node_guard = state.node.lock().unwrap();
+            assert_eq!(node_guard.blockchain.blocks.last(), Some(&block));
+            assert_eq!(node_guard.blockchain.balance_of("miner"), BLOCK_SUBSIDY);
+        }
+
+        // template da mesma altura, resolvido tarde demais
+        assert_eq!(
+            submit_block(&state, solve(competing)),
+            Err(BlockError::NotNextBlock { expected: 2, got: 1 })
+        );
+
+        // mexer no template depois de resolvido invalida o bloco
+        let next = state.node.lock().unwrap().blockchain.create_block_template("miner");
+        let mut tampered = solve(next);
+        tampered.transactions[0].amount += 1;
+        assert_eq!(submit_block(&state, tampered), Err(BlockError::InvalidMerkleRoot));
+        assert_eq!(state.node.lock().unwrap().blockchain.blocks.len(), 2);
+    }
+}
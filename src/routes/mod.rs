@@ -1,4 +1,6 @@
+pub mod block_routes;
 pub mod chain_routes;
+pub mod mining_routes;
 pub mod peer_routes;
 pub mod transaction_routes;
 pub mod wallet_routes;
\ No newline at end of file
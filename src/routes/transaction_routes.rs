@@ -1,8 +1,11 @@
 use axum::{
-    extract::{State, Json},
+    body::Bytes,
+    extract::{Path, State, Json},
     response::IntoResponse,
+    http::StatusCode,
 };
 use crate::AppState;
+use crate::blockchain::merkle::{verify_transaction_proof, TransactionProof};
 use crate::wallet::transaction::Transaction;
 use crate::errors::TransactionError;
 use serde::Deserialize;
@@ -12,6 +15,8 @@ pub struct CreateTransactionRequest {
     wallet_id: String,
     to_address: String,
     amount: u64,
+    #[serde(default)]
+    fee: u64,
 }
 
 pub async fn create_transaction_handler(
@@ -22,11 +27,12 @@ pub async fn create_transaction_handler(
     let wallet = wallets.get(&request.wallet_id)
         .ok_or(TransactionError::InvalidTx("Wallet not found".to_string()))?;
     
-    let tx = Transaction::new_signed(wallet, request.to_address, request.amount)?;
-    
     let mut node = state.node.lock().unwrap();
+    let nonce = node.blockchain.next_nonce(&wallet.address);
+    let tx = Transaction::new_signed_with_fee(wallet, request.to_address, request.amount, request.fee, nonce)?;
+    
     node.verify_signature(&tx)?;
-    node.receive_transaction(tx.clone());
+    node.receive_transaction(tx.clone())?;
     
     Ok(Json(serde_json::json!({
         "message": "Transaction created and added to mempool",
@@ -40,6 +46,55 @@ pub async fn receive_transaction_handler(
 ) -> Result<impl IntoResponse, TransactionError> {
     let mut node = state.node.lock().unwrap();
     node.verify_signature(&tx)?;
-    node.receive_transaction(tx);
+    node.receive_transaction(tx)?;
+    Ok("Transaction received")
+}
+
+/// POST /transaction/raw - mesma coisa que /transaction, mas no formato binário canônico
+pub async fn receive_raw_transaction_handler(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<impl IntoResponse, TransactionError> {
+    let tx = Transaction::from_bytes(&body)?;
+    let mut node = state.node.lock().unwrap();
+    node.verify_signature(&tx)?;
+    node.receive_transaction(tx)?;
     Ok("Transaction received")
-}
\ No newline at end of file
+}
+
+/// GET /tx/:hash/proof - prova de Merkle para clientes leves
+pub async fn get_transaction_proof_handler(
+    State(state): State<AppState>,
+    Path(tx_hash): Path<String>,
+) -> impl IntoResponse {
+    let node = state.node.lock().unwrap();
+    match node.blockchain.transaction_proof(&tx_hash) {
+        Some((proof, height)) => {
+            let tip = node.blockchain.blocks.len() as u64 - 1;
+            (StatusCode::OK, Json(serde_json::json!({
+                "proof": proof,
+                "height": height,
+                "confirmations": tip - height + 1
+            })))
+        }
+        None => (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "message": "Transaction not found in any block"
+        }))),
+    }
+}
+
+/// POST /tx/proof/verify - confere uma prova de Merkle (de outro nó, por exemplo)
+/// e diz se o bloco dela está na nossa chain ativa
+pub async fn verify_transaction_proof_handler(
+    State(state): State<AppState>,
+    Json(proof): Json<TransactionProof>,
+) -> impl IntoResponse {
+    let valid = verify_transaction_proof(&proof);
+    let node = state.node.lock().unwrap();
+    let in_active_chain = valid && node.blockchain.height_of(&proof.header.hash).is_some();
+    (StatusCode::OK, Json(serde_json::json!({
+        "tx_hash": proof.tx_hash,
+        "valid": valid,
+        "in_active_chain": in_active_chain
+    })))
+}
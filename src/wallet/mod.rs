@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod wallet;
 pub mod transaction;
\ No newline at end of file
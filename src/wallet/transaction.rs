@@ -1,26 +1,124 @@
 use secp256k1::{Secp256k1, Message, PublicKey}; 
 use secp256k1::ecdsa::Signature; 
 use sha2::{Sha256, Digest};
-use crate::wallet::wallet::Wallet;
+use crate::wallet::wallet::{address_from_public_key, Wallet};
 use serde::{Serialize, Deserialize};
-use crate::errors::TransactionError; 
+use crate::errors::{EncodingError, TransactionError}; 
+use crate::encoding::{Decoder, Encoder, ENCODING_VERSION};
+
+/// `from_address` usado pela transação coinbase (recompensa do minerador).
+pub const COINBASE_ADDRESS: &str = "coinbase";
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Transaction {
     pub from_address: String,   
     pub to_address: String,
     pub amount: u64,
+    // pago ao minerador, além de amount
+    pub fee: u64,
+    // sequência por remetente; na coinbase é a altura do bloco
+    pub nonce: u64,
     pub public_key: Option<PublicKey>,
     pub signature: Option<Signature>,
 }
 
 impl Transaction {
-    fn payload_string(&self) -> String {
-        format!("{}|{}|{}", self.from_address, self.to_address, self.amount)
+    /// Bytes assinados: codificação canônica dos campos, sem chave e assinatura.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        Encoder::new()
+            .u8(ENCODING_VERSION)
+            .string(&self.from_address)
+            .string(&self.to_address)
+            .u64(self.amount)
+            .u64(self.fee)
+            .u64(self.nonce)
+            .finish()
+    }
+
+    /// Codificação binária canônica da transação completa.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let public_key = self.public_key.map(|key| key.serialize());
+        let signature = self.signature.map(|sig| sig.serialize_compact());
+        Encoder::new()
+            .u8(ENCODING_VERSION)
+            .string(&self.from_address)
+            .string(&self.to_address)
+            .u64(self.amount)
+            .u64(self.fee)
+            .u64(self.nonce)
+            .option(public_key.as_ref().map(|bytes| &bytes[..]))
+            .option(signature.as_ref().map(|bytes| &bytes[..]))
+            .finish()
+    }
+
+    /// Decodifica e rejeita qualquer entrada que não seja exatamente `to_bytes()`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Transaction, EncodingError> {
+        let mut decoder = Decoder::new(bytes);
+        let tx = Transaction::decode(&mut decoder)?;
+        decoder.finish()?;
+        Ok(tx)
+    }
+
+    pub fn decode(decoder: &mut Decoder) -> Result<Transaction, EncodingError> {
+        decoder.version()?;
+        let from_address = decoder.string()?;
+        let to_address = decoder.string()?;
+        let amount = decoder.u64()?;
+        let fee = decoder.u64()?;
+        let nonce = decoder.u64()?;
+
+        let public_key = match decoder.option()? {
+            None => None,
+            Some(bytes) => {
+                // só a forma comprimida (33 bytes) é canônica
+                if bytes.len() != 33 {
+                    return Err(EncodingError::NonCanonical("public key must be 33 bytes".to_string()));
+                }
+                let key = PublicKey::from_slice(bytes)
+                    .map_err(|_| EncodingError::NonCanonical("invalid public key".to_string()))?;
+                Some(key)
+            }
+        };
+
+        let signature = match decoder.option()? {
+            None => None,
+            Some(bytes) => {
+                let sig = Signature::from_compact(bytes)
+                    .map_err(|_| EncodingError::NonCanonical("invalid signature".to_string()))?;
+                // high-S é maleável, só aceitamos a forma normalizada
+                let mut normalized = sig;
+                normalized.normalize_s();
+                if normalized != sig {
+                    return Err(EncodingError::NonCanonical("signature is not low-S".to_string()));
+                }
+                Some(sig)
+            }
+        };
+
+        Ok(Transaction {
+            from_address,
+            to_address,
+            amount,
+            fee,
+            nonce,
+            public_key,
+            signature,
+        })
     }
 
     //não tem que ser from Wallet to: Wallet?
-    pub fn new_signed(from_wallet: &Wallet, to_address: String, amount: u64) -> Result<Transaction, TransactionError> {
+    pub fn new_signed(from_wallet: &Wallet, to_address: String, amount: u64, nonce: u64) -> Result<Transaction, TransactionError> {
+        Transaction::new_signed_with_fee(from_wallet, to_address, amount, 0, nonce)
+    }
+
+    //igual a new_signed, pagando `fee` ao minerador
+    pub fn new_signed_with_fee(
+        from_wallet: &Wallet,
+        to_address: String,
+        amount: u64,
+        fee: u64,
+        nonce: u64,
+    ) -> Result<Transaction, TransactionError> {
         if amount == 0 {
             return Err(TransactionError::InvalidAmount);
         }
@@ -30,29 +128,78 @@ impl Transaction {
             from_address, 
             to_address,
             amount,
+            fee,
+            nonce,
             public_key: Some(from_wallet.public_key),
             signature: None, //Sign later
         };
 
-        let data_string = tx.payload_string();
-
-        let sig = sign_data(from_wallet, data_string.as_bytes());
+        let sig = sign_data(from_wallet, &tx.signing_payload());
 
         tx.signature = Some(sig);
 
         Ok(tx)
     }
 
+    //allocation credited by the genesis block, it has no sender and no signature
+    pub fn genesis_allocation(to_address: String, amount: u64) -> Transaction {
+        Transaction {
+            from_address: "0".to_string(),
+            to_address,
+            amount,
+            fee: 0,
+            nonce: 0,
+            public_key: None,
+            signature: None,
+        }
+    }
+
+    //reward paid to the miner, first transaction of every mined block
+    //the nonce is the block height, so two coinbases never share a tx_hash
+    pub fn new_coinbase(to_address: String, amount: u64, height: u64) -> Transaction {
+        Transaction {
+            from_address: COINBASE_ADDRESS.to_string(),
+            to_address,
+            amount,
+            fee: 0,
+            nonce: height,
+            public_key: None,
+            signature: None,
+        }
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.from_address == COINBASE_ADDRESS && self.public_key.is_none() && self.signature.is_none()
+    }
+
+    /// from_address tem que ser o endereço derivado de public_key; senão qualquer
+    /// um assinaria "em nome" de outro endereço com a própria chave.
+    pub fn check_address_binding(&self) -> Result<(), TransactionError> {
+        let public_key = self
+            .public_key
+            .as_ref()
+            .ok_or(TransactionError::InvalidTx("Missing public key".to_string()))?;
+        let derived_address = address_from_public_key(public_key);
+        if derived_address != self.from_address {
+            return Err(TransactionError::AddressMismatch {
+                from_address: self.from_address.clone(),
+                derived_address,
+            });
+        }
+        Ok(())
+    }
+
     //to add transactions to the mempool we check if they are valid
     pub fn is_valid(&self) -> bool {
         if self.public_key.is_none() || self.signature.is_none() {
             return false;
         }
-
-        let data_string = self.payload_string();
+        if self.check_address_binding().is_err() {
+            return false;
+        }
 
         let mut hasher = Sha256::new();
-        hasher.update(data_string.as_bytes());
+        hasher.update(self.signing_payload());
         let result = hasher.finalize();
 
         let message = Message::from_digest_slice(&result).expect("Hash deve ter 32 bytes");
@@ -64,37 +211,38 @@ impl Transaction {
         secp.verify_ecdsa(&message, sig, pub_key).is_ok()
     }
 
-    pub fn tx_hash(&self) -> String {
-
-        let mut data = format!("{}|{}|{}", 
-            self.from_address, 
-            self.to_address, 
-            self.amount
-        );
+    /// Quanto o remetente gasta: amount + fee. None se estourar.
+    pub fn total_cost(&self) -> Option<u64> {
+        self.amount.checked_add(self.fee)
+    }
 
-        if let Some(pub_key) = &self.public_key {
-            // Converte para bytes comprimidos ou não
-            // Normalmente, `pub_key.serialize()` dá 33 bytes (comprimido) ou 65 (descomprimido)
-            let pub_key_bytes = pub_key.serialize();
-            data.push_str(&hex::encode(pub_key_bytes));
-        }
+    /// Tamanho da codificação canônica, usado no limite do bloco e no fee rate.
+    pub fn size(&self) -> usize {
+        self.to_bytes().len()
+    }
 
-        if let Some(sig) = &self.signature {
-            // ECDSA compact: 64 bytes. Some crates usam 72 etc. 
-            // Em secp256k1, `sig.serialize_compact()` dá 64 bytes
-            let sig_bytes = sig.serialize_compact();
-            data.push_str(&hex::encode(sig_bytes));
-        }
+    /// Ordena por fee/size. Recodifica as duas transações; quem já tem o tamanho
+    /// guardado (o mempool) usa `cmp_fee_rates` direto.
+    #[allow(dead_code)]
+    pub fn cmp_fee_rate(&self, other: &Transaction) -> std::cmp::Ordering {
+        cmp_fee_rates((self.fee, self.size()), (other.fee, other.size()))
+    }
 
+    //sha256 da codificação canônica (inclui chave pública e assinatura)
+    pub fn tx_hash(&self) -> String {
         let mut hasher = Sha256::new();
-        hasher.update(data.as_bytes());
-        let result = hasher.finalize();
-
-        hex::encode(result)
-    }  
+        hasher.update(self.to_bytes());
+        hex::encode(hasher.finalize())
+    }
 
 }
 
+/// Compara dois fee rates (fee, tamanho) sem ponto flutuante (multiplicação cruzada).
+pub fn cmp_fee_rates((fee, size): (u64, usize), (other_fee, other_size): (u64, usize)) -> std::cmp::Ordering {
+    let ours = fee as u128 * other_size as u128;
+    let theirs = other_fee as u128 * size as u128;
+    ours.cmp(&theirs)
+}
 
 pub fn sign_data(wallet: &Wallet, data: &[u8]) -> Signature {
     let mut hasher = Sha256::new();
@@ -106,9 +254,7 @@ pub fn sign_data(wallet: &Wallet, data: &[u8]) -> Signature {
     .expect("Hash deve ter 32 bytes");
 
     let secp = Secp256k1::new();
-    let signature = secp.sign_ecdsa(&message, &wallet.secret_key);
-
-    signature
+    secp.sign_ecdsa(&message, &wallet.secret_key)
 }
 
 #[cfg(test)]
@@ -125,7 +271,7 @@ mod tests {
         let to_wallet = generate_wallet();  
 
         // Tenta criar transação com amount=50
-        let tx_result = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 50);
+        let tx_result = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 50, 0);
         assert!(tx_result.is_ok(), "Esperado que a transação seja criada com sucesso");
 
         let tx = tx_result.unwrap();
@@ -147,7 +293,7 @@ mod tests {
         let from_wallet = generate_wallet();
         let to_wallet = generate_wallet();
 
-        let tx_result = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 0);
+        let tx_result = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 0, 0);
         assert!(tx_result.is_err(), "Esperado erro pois amount=0");
 
         if let Err(e) = tx_result {
@@ -155,8 +301,8 @@ mod tests {
                 TransactionError::InvalidAmount => {
                     // Erro esperado, teste passa
                 }
-                TransactionError::InvalidSignature(_) | TransactionError::InvalidTx(_) => {
-                    panic!("Esperado TransactionError::InvalidAmount, mas recebeu outro erro");
+                other => {
+                    panic!("Esperado TransactionError::InvalidAmount, mas recebeu {:?}", other);
                 }
             }
         }
@@ -168,7 +314,7 @@ mod tests {
         let to_wallet = generate_wallet();
 
         // Cria transação válida
-        let mut tx = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 10)
+        let mut tx = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 10, 0)
             .expect("Deveria criar ok");
         assert!(tx.is_valid(), "Deveria ser válida inicialmente");
 
@@ -186,12 +332,17 @@ mod tests {
         let from_wallet = generate_wallet();
         let to_wallet = generate_wallet();
 
-        let tx = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 100)
+        let tx = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 100, 0)
             .expect("Cria transação com amount=100");
         
         let hash1 = tx.tx_hash();
         assert!(!hash1.is_empty(), "Hash não deve ser vazio");
 
+        // o nonce também entra no hash
+        let mut replay = tx.clone();
+        replay.nonce = 1;
+        assert_ne!(hash1, replay.tx_hash(), "Hash deve mudar quando o nonce muda");
+
         // Clone e altera o amount para ver se o hash muda
         let mut tx2 = tx.clone();
         tx2.amount = 200;
@@ -211,15 +362,84 @@ mod tests {
         assert_ne!(hash1, hash3, "Hash deve mudar se assinatura muda");
     }
 
+    #[test]
+    fn test_coinbase_is_not_a_valid_signed_transaction() {
+        let miner = generate_wallet();
+        let coinbase = Transaction::new_coinbase(miner.address.clone(), 50, 1);
+
+        assert!(coinbase.is_coinbase());
+        // sem assinatura, nunca pode entrar no mempool
+        assert!(!coinbase.is_valid());
+
+        let tx = Transaction::new_signed(&miner, "Bob".to_string(), 10, 0).unwrap();
+        assert!(!tx.is_coinbase());
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let from_wallet = generate_wallet();
+        let tx = Transaction::new_signed(&from_wallet, "Bob".to_string(), 42, 0).unwrap();
+        let decoded = Transaction::from_bytes(&tx.to_bytes()).expect("roundtrip");
+        assert_eq!(decoded, tx);
+        assert!(decoded.is_valid());
+
+        let coinbase = Transaction::new_coinbase("Miner".to_string(), 50, 1);
+        assert_eq!(Transaction::from_bytes(&coinbase.to_bytes()).unwrap(), coinbase);
+    }
+
+    #[test]
+    fn test_binary_rejects_non_canonical() {
+        let from_wallet = generate_wallet();
+        let tx = Transaction::new_signed(&from_wallet, "Bob".to_string(), 42, 0).unwrap();
+        let bytes = tx.to_bytes();
+
+        // bytes sobrando
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(Transaction::from_bytes(&trailing), Err(EncodingError::TrailingBytes(1)));
+
+        // truncado
+        assert_eq!(Transaction::from_bytes(&bytes[..bytes.len() - 1]), Err(EncodingError::UnexpectedEnd));
+
+        // versão errada
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = 2;
+        assert_eq!(Transaction::from_bytes(&wrong_version), Err(EncodingError::UnsupportedVersion(2)));
+
+        // chave pública na forma descomprimida
+        let uncompressed = tx.public_key.unwrap().serialize_uncompressed();
+        let non_canonical = Encoder::new()
+            .u8(ENCODING_VERSION)
+            .string(&tx.from_address)
+            .string(&tx.to_address)
+            .u64(tx.amount)
+            .u64(tx.fee)
+            .u64(tx.nonce)
+            .option(Some(&uncompressed))
+            .option(None)
+            .finish();
+        assert!(matches!(Transaction::from_bytes(&non_canonical), Err(EncodingError::NonCanonical(_))));
+    }
+
+    #[test]
+    fn test_signing_payload_is_unambiguous() {
+        // com o formato antigo "from|to|amount" estas duas assinavam a mesma string
+        let mut a = Transaction::new_coinbase("b|c".to_string(), 1, 1);
+        a.from_address = "a".to_string();
+        let mut b = Transaction::new_coinbase("c".to_string(), 1, 1);
+        b.from_address = "a|b".to_string();
+        assert_ne!(a.signing_payload(), b.signing_payload());
+    }
+
     #[test]
     fn test_tx_hash_is_deterministic() {
         let from_wallet = generate_wallet();
         let to_wallet = generate_wallet();
 
         // Cria duas transações idênticas (mesmo from, to, amount)
-        let tx1 = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 10)
+        let tx1 = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 10, 0)
             .expect("ok");
-        let tx2 = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 10)
+        let tx2 = Transaction::new_signed(&from_wallet, to_wallet.address.clone(), 10, 0)
             .expect("ok");
 
         // Se o from_wallet gera a mesma chave pública e assina do mesmo jeito,
@@ -234,4 +454,51 @@ mod tests {
         // Então esse teste serve para ilustrar a ideia. Se sua sign_data é determinística, descomente:
         assert_eq!(tx1.tx_hash(), tx2.tx_hash(), "Hash should be same if signature is deterministic");
     }
+
+    #[test]
+    fn test_nonce_is_covered_by_signature() {
+        let from_wallet = generate_wallet();
+        let mut tx = Transaction::new_signed(&from_wallet, "Bob".to_string(), 10, 3).unwrap();
+        assert_eq!(tx.nonce, 3);
+        assert!(tx.is_valid());
+
+        // trocar o nonce invalida a assinatura: não dá para reaproveitar a transação
+        tx.nonce = 4;
+        assert!(!tx.is_valid());
+    }
+
+    #[test]
+    fn test_fee_is_signed_and_ranks_by_rate() {
+        let from_wallet = generate_wallet();
+        let cheap = Transaction::new_signed_with_fee(&from_wallet, "Bob".to_string(), 10, 1, 0).unwrap();
+        let rich = Transaction::new_signed_with_fee(&from_wallet, "Bob".to_string(), 10, 5, 0).unwrap();
+        assert!(rich.is_valid());
+        assert_eq!(rich.total_cost(), Some(15));
+        assert_eq!(rich.cmp_fee_rate(&cheap), std::cmp::Ordering::Greater);
+
+        // o minerador não pode aumentar a taxa depois da assinatura
+        let mut tampered = cheap.clone();
+        tampered.fee = 100;
+        assert!(!tampered.is_valid());
+    }
+
+    #[test]
+    fn test_from_address_must_match_public_key() {
+        let victim = generate_wallet();
+        let attacker = generate_wallet();
+
+        // o atacante assina com a própria chave uma transação "de" victim
+        let mut tx = Transaction::new_signed(&attacker, attacker.address.clone(), 10, 0).unwrap();
+        tx.from_address = victim.address.clone();
+        tx.signature = Some(sign_data(&attacker, &tx.signing_payload()));
+
+        assert_eq!(
+            tx.check_address_binding(),
+            Err(TransactionError::AddressMismatch {
+                from_address: victim.address.clone(),
+                derived_address: attacker.address.clone(),
+            })
+        );
+        assert!(!tx.is_valid());
+    }
 }
@@ -17,18 +17,21 @@ impl Wallet {
     }
 }
 
+/// Endereço de uma chave: hex(sha256(chave pública comprimida)).
+pub fn address_from_public_key(public_key: &PublicKey) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(public_key.serialize());
+    let result = hasher.finalize();
+    hex::encode(result)
+}
+
 pub fn generate_wallet() -> Wallet {
     let secp = Secp256k1::new();
     let mut rng = OsRng;
 
     let (secret_key, public_key) = secp.generate_keypair(&mut rng);
 
-    let pubkey_serialized = public_key.serialize();
-
-    let mut hasher = Sha256::new();
-    hasher.update(pubkey_serialized);
-    let result = hasher.finalize();
-    let address = hex::encode(result);
+    let address = address_from_public_key(&public_key);
     Wallet {
         secret_key,
         public_key,
@@ -75,6 +78,12 @@ mod tests {
                    "Secret keys devem ser diferentes");
     }
 
+    #[test]
+    fn test_address_is_derived_from_public_key() {
+        let wallet = generate_wallet();
+        assert_eq!(address_from_public_key(&wallet.public_key), wallet.address);
+    }
+
     #[test]
     fn test_print_info() {
         let wallet = generate_wallet();